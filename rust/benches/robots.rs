@@ -0,0 +1,81 @@
+//! Benchmarks for the parsing and simulation halves of the pipeline.
+//!
+//! Run with `cargo bench`. The workload is 10k robots, each with a long
+//! instruction string, on the largest grid that we allow.
+
+use anyhow::Result;
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use redbadger_challenge::{
+    check_robots, drive_robots, Grid, Instruction, ResultOfIteratorOfResult, Robot,
+};
+use std::convert::TryFrom;
+use std::hint::black_box;
+
+const ROBOTS: usize = 10_000;
+const INSTRUCTIONS: &str =
+    "FRFFLFFFRRFLLFFRRFLLFRFRFRFRFLFLFLFLFFFFRRRRFFFFLLLLFRFLFRFLFFRRFFLLFRFRFFFLLFFRFLFRFFFLFRFRRFLRFRL";
+const BEARINGS: [&str; 4] = ["N", "E", "S", "W"];
+
+fn input() -> Vec<String> {
+    let mut lines = vec!["50 50".to_owned()];
+    for i in 0..ROBOTS {
+        lines.push(format!("{} {} {}", i % 51, (i / 51) % 51, BEARINGS[i % 4]));
+        lines.push(INSTRUCTIONS.to_owned());
+    }
+    lines
+}
+
+fn lines(input: &[String]) -> impl Iterator<Item = Result<String>> + '_ {
+    input.iter().cloned().map(Ok)
+}
+
+fn parsing(c: &mut Criterion) {
+    let input = input();
+    let mut group = c.benchmark_group("parsing");
+    group.throughput(Throughput::Elements(ROBOTS as u64));
+    group.bench_function("check_robots", |b| {
+        b.iter(|| check_robots(lines(black_box(&input))).unwrap())
+    });
+    group.finish();
+}
+
+fn simulation(c: &mut Criterion) {
+    let grid: Grid = "50 50".parse().unwrap();
+    let robots = (0..ROBOTS)
+        .map(|i| format!("{} {} {}", i % 51, (i / 51) % 51, BEARINGS[i % 4]).parse())
+        .collect::<Result<Vec<Robot>>>()
+        .unwrap();
+    let instructions = INSTRUCTIONS
+        .chars()
+        .map(Instruction::try_from)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+
+    let mut group = c.benchmark_group("simulation");
+    group.throughput(Throughput::Elements(ROBOTS as u64));
+    group.bench_function("try_all_instructions", |b| {
+        b.iter(|| {
+            for robot in &robots {
+                let _ = black_box(robot.clone().try_all_instructions(&grid, &instructions));
+            }
+        })
+    });
+    group.finish();
+}
+
+fn end_to_end(c: &mut Criterion) {
+    let input = input();
+    let mut group = c.benchmark_group("end_to_end");
+    group.throughput(Throughput::Elements(ROBOTS as u64));
+    group.bench_function("drive_robots", |b| {
+        b.iter(|| {
+            drive_robots(lines(black_box(&input)))
+                .flatten_to_iterator()
+                .count()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, parsing, simulation, end_to_end);
+criterion_main!(benches);
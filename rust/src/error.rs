@@ -0,0 +1,86 @@
+//! Errors that callers might want to match on, rather than just print.
+//!
+//! Everything in the crate still returns anyhow::Error, so these are found
+//! with downcast_ref(). Errors from drive_robots() and friends are wrapped
+//! in a LineError that says where they came from.
+
+use crate::MAX_COORDINATE;
+use std::fmt::Display;
+
+/// Something wrong with the shape of a line of input.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    MissingX,
+    MissingY,
+    MissingBearing,
+    /// A coordinate that isn't a whole number that fits in an i32.
+    InvalidNumber,
+    /// The name of the kind of line, like "grid".
+    TooManyFields(&'static str),
+    /// The name of the kind of line, like "grid".
+    BlankLine(&'static str),
+    NegativeCoordinate,
+    /// Bigger than MAX_COORDINATE, or (for a grid's minimum) smaller than
+    /// minus MAX_COORDINATE.
+    CoordinateTooLarge,
+    /// A grid's minimum is above or to the right of its maximum.
+    MinAboveMax,
+    BadBearing,
+    BadInstruction,
+    EmptyInput,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        use ParseError::*;
+        match self {
+            MissingX => write!(f, "missing x coordinate"),
+            MissingY => write!(f, "missing y coordinate"),
+            MissingBearing => write!(f, "missing bearing"),
+            InvalidNumber => write!(f, "coordinate must be a whole number"),
+            TooManyFields(line) => write!(f, "{} line has too many fields", line),
+            BlankLine(line) => write!(f, "{} line is blank", line),
+            NegativeCoordinate => write!(f, "grid coordinate must not be negative"),
+            CoordinateTooLarge => {
+                write!(f, "grid coordinate exceeds maximum of {}", MAX_COORDINATE)
+            }
+            MinAboveMax => write!(f, "grid minimum must not be greater than its maximum"),
+            BadBearing => write!(f, "Bearing must be one of N, NE, E, SE, S, SW, W, or NW"),
+            BadInstruction => write!(f, "instruction must be F, B, L, R, U, S, H, or Tx,y"),
+            EmptyInput => write!(f, "input must not be empty"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// An error, and the number of the line of input that caused it.
+#[derive(Debug)]
+pub struct LineError {
+    pub line: usize,
+    pub error: anyhow::Error,
+}
+
+impl Display for LineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.error)
+    }
+}
+
+impl std::error::Error for LineError {}
+
+/// An error, and which robot (counting from 1) caused it, for input that
+/// doesn't come in lines, like a Scenario.
+#[derive(Debug)]
+pub struct RobotError {
+    pub robot: usize,
+    pub error: anyhow::Error,
+}
+
+impl Display for RobotError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "robot {}: {}", self.robot, self.error)
+    }
+}
+
+impl std::error::Error for RobotError {}
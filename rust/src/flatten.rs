@@ -4,22 +4,24 @@
 //! body) so I wrote this adaptor. The entire point of its existence is
 //! to convert the output of drive_robots() from
 //! `Result<Iterator<Result<String>>>`) to `Iterator<Result<String>>`.
+//! (It doesn't care what is inside the inner Result, so it works for
+//! simulate_robots() too.)
 
 use anyhow::{Error, Result};
 
-pub(crate) enum FlattenedIteratorOfResult<T>
+pub enum FlattenedIteratorOfResult<I>
 where
-    T: Iterator<Item = Result<String>> + Sized,
+    I: Iterator,
 {
     Err(Option<Error>),
-    Ok(T),
+    Ok(I),
 }
 
-impl<T> Iterator for FlattenedIteratorOfResult<T>
+impl<I, T> Iterator for FlattenedIteratorOfResult<I>
 where
-    T: Iterator<Item = Result<String>> + Sized,
+    I: Iterator<Item = Result<T>>,
 {
-    type Item = Result<String>;
+    type Item = Result<T>;
     fn next(&mut self) -> Option<Self::Item> {
         match self {
             FlattenedIteratorOfResult::Ok(iter) => iter.next(),
@@ -28,18 +30,18 @@ where
     }
 }
 
-pub(crate) trait ResultOfIteratorOfResult<T>
+pub trait ResultOfIteratorOfResult<I>
 where
-    T: Iterator<Item = Result<String>>,
+    I: Iterator,
 {
-    fn flatten_to_iterator(self) -> FlattenedIteratorOfResult<T>;
+    fn flatten_to_iterator(self) -> FlattenedIteratorOfResult<I>;
 }
 
-impl<T> ResultOfIteratorOfResult<T> for Result<T>
+impl<I, T> ResultOfIteratorOfResult<I> for Result<I>
 where
-    T: Iterator<Item = Result<String>>,
+    I: Iterator<Item = Result<T>>,
 {
-    fn flatten_to_iterator(self) -> FlattenedIteratorOfResult<T> {
+    fn flatten_to_iterator(self) -> FlattenedIteratorOfResult<I> {
         match self {
             Ok(iter) => FlattenedIteratorOfResult::Ok(iter),
             Err(err) => FlattenedIteratorOfResult::Err(Some(err)),
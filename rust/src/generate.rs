@@ -0,0 +1,128 @@
+//! Random inputs, for fuzzing and demos.
+
+use crate::{Instruction, Rotation, MAX_COORDINATE, MAX_INSTRUCTIONS};
+use std::fmt::Write;
+
+/// Makes up an input with a grid and `robots` robots, using only the
+/// syntax from the challenge. The same seed always gives the same input.
+pub fn generate(seed: u64, robots: usize) -> String {
+    let mut rng = SplitMix64(seed);
+    let max_x = rng.below(MAX_COORDINATE as u64 + 1);
+    let max_y = rng.below(MAX_COORDINATE as u64 + 1);
+
+    let mut input = format!("{} {}\n", max_x, max_y);
+    for _ in 0..robots {
+        let x = rng.below(max_x + 1);
+        let y = rng.below(max_y + 1);
+        let bearing = ["N", "E", "S", "W"][rng.below(4) as usize];
+        writeln!(input, "{} {} {}", x, y, bearing).unwrap();
+
+        let length = 1 + rng.below(MAX_INSTRUCTIONS as u64 - 1);
+        for _ in 0..length {
+            input.push(['L', 'R', 'F'][rng.below(3) as usize]);
+        }
+        input.push('\n');
+    }
+    input
+}
+
+/// Inserts, deletes or swaps one instruction, for comparing the simulator
+/// against a reference on lots of nearly-the-same robots. Inserted
+/// instructions are L, R or F, and the result is never too long to be
+/// valid input: instructions that are already too long are cut short
+/// before they are mutated. The same seed always gives the same result.
+pub fn mutate_instructions(instructions: &[Instruction], seed: u64) -> Vec<Instruction> {
+    let mut rng = SplitMix64(seed);
+    let kept = &instructions[..instructions.len().min(MAX_INSTRUCTIONS - 1)];
+    loop {
+        let mut mutated = kept.to_vec();
+        let len = mutated.len() as u64;
+        let can_insert = mutated.len() < MAX_INSTRUCTIONS - 1;
+        match rng.below(3) {
+            0 if len > 0 => {
+                mutated.remove(rng.below(len) as usize);
+            }
+            1 if len > 1 => mutated.swap(rng.below(len) as usize, rng.below(len) as usize),
+            _ if can_insert => {
+                let instruction = match rng.below(3) {
+                    0 => Instruction::Turn(Rotation::L),
+                    1 => Instruction::Turn(Rotation::R),
+                    _ => Instruction::F,
+                };
+                mutated.insert(rng.below(len + 1) as usize, instruction);
+            }
+            _ => continue,
+        }
+        // Swapping two of the same instruction doesn't change anything.
+        if mutated != instructions {
+            return mutated;
+        }
+    }
+}
+
+/// The generator from <https://prng.di.unimi.it/splitmix64.c>. It's fast,
+/// small, and good enough for making up robots.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number from 0 to n - 1. Slightly biased, which doesn't matter here.
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{drive_robots, parse_instructions, ResultOfIteratorOfResult};
+    use anyhow::Result;
+
+    #[test]
+    fn generated_input_can_be_driven() -> Result<()> {
+        for seed in 0..20 {
+            let input = generate(seed, 50);
+            let lines = input.lines().map(|l| Ok(l.to_owned()));
+            let output = drive_robots(lines)
+                .flatten_to_iterator()
+                .collect::<Result<Vec<String>>>()?;
+            assert_eq!(output.len(), 50);
+        }
+        assert_eq!(generate(7, 3), generate(7, 3));
+        Ok(())
+    }
+
+    #[test]
+    fn mutated_instructions_are_still_valid() -> Result<()> {
+        let original = parse_instructions("FRRFLLFFRRFLL")?;
+        let allowed = parse_instructions("LRF")?;
+        for seed in 0..50 {
+            let mutated = mutate_instructions(&original, seed);
+            assert_ne!(mutated, original);
+            assert!((12..=14).contains(&mutated.len()));
+            assert!(mutated.iter().all(|i| allowed.contains(i)));
+        }
+        assert_eq!(
+            mutate_instructions(&original, 3),
+            mutate_instructions(&original, 3)
+        );
+
+        let longest = vec![Instruction::F; MAX_INSTRUCTIONS - 1];
+        for seed in 0..20 {
+            assert!(mutate_instructions(&longest, seed).len() < MAX_INSTRUCTIONS);
+        }
+        let too_long = vec![Instruction::F; MAX_INSTRUCTIONS + 10];
+        for seed in 0..20 {
+            assert!(mutate_instructions(&too_long, seed).len() < MAX_INSTRUCTIONS);
+        }
+        assert_eq!(mutate_instructions(&[], 0).len(), 1);
+        Ok(())
+    }
+}
@@ -0,0 +1,302 @@
+//! Robots on a grid of hexagons rather than squares.
+//!
+//! Cells use axial coordinates: `x` runs East and `y` runs North-East, so a
+//! Grid of "5 3" is a rhombus of hexagons rather than a rectangle. Edges,
+//! obstacles and wrapping all work the same as they do for square robots,
+//! because they only care about coordinates. Scents follow the grid's
+//! ScentPolicy, using each bearing's Heading.
+
+#[cfg(feature = "std")]
+use crate::ParseError;
+use crate::{until_halt, Coords, Grid, Heading, Instruction, Rotation};
+#[cfg(feature = "std")]
+use anyhow::{Error, Result};
+#[cfg(feature = "std")]
+use enum_display_derive::Display;
+#[cfg(feature = "std")]
+use std::fmt::Display;
+#[cfg(feature = "std")]
+use std::str::FromStr;
+
+/// One of the six edges of a hexagon, with points at North and South.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Display))]
+pub enum HexBearing {
+    NE,
+    E,
+    SE,
+    SW,
+    W,
+    NW,
+}
+
+#[cfg(feature = "std")]
+impl FromStr for HexBearing {
+    type Err = anyhow::Error;
+
+    fn from_str(input: &str) -> Result<Self> {
+        use HexBearing::*;
+        match input.to_ascii_uppercase().as_str() {
+            "NE" => Ok(NE),
+            "E" => Ok(E),
+            "SE" => Ok(SE),
+            "SW" => Ok(SW),
+            "W" => Ok(W),
+            "NW" => Ok(NW),
+            _ => Err(Error::msg(
+                "HexBearing must be one of NE, E, SE, SW, W, or NW",
+            )),
+        }
+    }
+}
+
+impl HexBearing {
+    /// L and R turn by 60 degrees, so six of them get back to the start.
+    pub fn rotate(self, rotation: &Rotation) -> HexBearing {
+        use HexBearing::*;
+        use Rotation::*;
+
+        match (self, rotation) {
+            (NE, L) => NW,
+            (E, L) => NE,
+            (SE, L) => E,
+            (SW, L) => SE,
+            (W, L) => SW,
+            (NW, L) => W,
+            (NW, R) => NE,
+            (NE, R) => E,
+            (E, R) => SE,
+            (SE, R) => SW,
+            (SW, R) => W,
+            (W, R) => NW,
+        }
+    }
+
+    /// Which way this is, for scents. The edges are between the points
+    /// at North and South, so none of them face straight up or down.
+    pub fn heading(self) -> Heading {
+        use HexBearing::*;
+        match self {
+            NE => 30,
+            E => 90,
+            SE => 150,
+            SW => 210,
+            W => 270,
+            NW => 330,
+        }
+    }
+
+    /// The opposite edge.
+    pub fn reverse(self) -> HexBearing {
+        self.rotate(&Rotation::R)
+            .rotate(&Rotation::R)
+            .rotate(&Rotation::R)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HexRobot {
+    pub coords: Coords,
+    pub bearing: HexBearing,
+}
+
+#[cfg(feature = "std")]
+impl FromStr for HexRobot {
+    type Err = anyhow::Error;
+
+    fn from_str(position_line: &str) -> Result<Self> {
+        let position_line = position_line.trim();
+        let mut split = position_line.split_whitespace();
+        let coords = Coords::try_from_iterator(&mut split)?;
+        let bearing = split.next().ok_or(ParseError::MissingBearing)?.parse()?;
+        if split.next().is_some() {
+            return Err(ParseError::TooManyFields("position").into());
+        }
+        Ok(HexRobot { coords, bearing })
+    }
+}
+
+/// The same format as a position line in the input.
+#[cfg(feature = "std")]
+impl Display for HexRobot {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} {} {}", self.coords.x, self.coords.y, self.bearing)
+    }
+}
+
+impl HexRobot {
+    /// Moves `steps` cells across the edge that the robot is facing
+    /// (negative steps move backwards). Returns None if a coordinate would
+    /// overflow.
+    fn checked_move(mut self, steps: i32) -> Option<Self> {
+        use HexBearing::*;
+        let (dx, dy) = match self.bearing {
+            NE => (0, 1),
+            E => (1, 0),
+            SE => (1, -1),
+            SW => (0, -1),
+            W => (-1, 0),
+            NW => (-1, 1),
+        };
+        self.coords.x = self.coords.x.checked_add(dx * steps)?;
+        self.coords.y = self.coords.y.checked_add(dy * steps)?;
+        Some(self)
+    }
+
+    /// Where `instruction` would take the robot if there were no edges,
+    /// scents or other robots to worry about.
+    fn destination(&self, grid: &Grid, instruction: &Instruction) -> Option<HexRobot> {
+        let mut next = match instruction {
+            Instruction::Turn(t) => HexRobot {
+                bearing: self.bearing.rotate(t),
+                ..self.clone()
+            },
+            Instruction::U => HexRobot {
+                bearing: self.bearing.reverse(),
+                ..self.clone()
+            },
+            Instruction::F => self.clone().checked_move(grid.stride)?,
+            Instruction::B => self.clone().checked_move(-grid.stride)?,
+            Instruction::Forward(steps) => {
+                self.clone().checked_move(steps.checked_mul(grid.stride)?)?
+            }
+            Instruction::Scent | Instruction::Halt => self.clone(),
+            Instruction::Teleport(x, y) => HexRobot {
+                coords: Coords { x: *x, y: *y },
+                ..self.clone()
+            },
+        };
+        if grid.wrap {
+            next.coords = grid.wrapped(next.coords);
+        }
+        Some(next)
+    }
+
+    /// Returns either the position that the robot ended up at or the
+    /// position where it was before it fell off the board.
+    pub fn try_next_instruction(
+        self,
+        grid: &Grid,
+        instruction: &Instruction,
+    ) -> core::result::Result<HexRobot, HexRobot> {
+        if let Instruction::Forward(steps) = instruction {
+            let mut current = self;
+            for _ in 0..*steps {
+                let next = current
+                    .clone()
+                    .try_next_instruction(grid, &Instruction::F)?;
+                if next == current {
+                    break;
+                }
+                current = next;
+            }
+            return Ok(current);
+        }
+        let next = self.destination(grid, instruction);
+        match instruction {
+            Instruction::Turn(_) | Instruction::U | Instruction::Scent | Instruction::Halt => {
+                Ok(next.unwrap_or(self))
+            }
+            Instruction::F | Instruction::B | Instruction::Teleport(..) => match next {
+                Some(next)
+                    if grid.occupied.contains(&next.coords)
+                        || grid.obstacles.contains(&next.coords) =>
+                {
+                    Ok(self)
+                }
+                Some(next) if grid.ghost || grid.contains(next.coords.x, next.coords.y) => Ok(next),
+                _ if grid.scents.has_scent(&self.coords, self.bearing.heading()) => Ok(self),
+                _ => Err(self),
+            },
+            Instruction::Forward(_) => unreachable!(),
+        }
+    }
+
+    /// Returns either the position that the robot ended up at or the
+    /// position where it was before it fell off the board.
+    pub fn try_all_instructions(
+        self,
+        grid: &Grid,
+        instructions: &[Instruction],
+    ) -> core::result::Result<HexRobot, HexRobot> {
+        let mut current = self;
+        for instruction in until_halt(instructions) {
+            current = current.try_next_instruction(grid, instruction)?;
+        }
+        Ok(current)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PerCellAndBearing;
+
+    #[test]
+    fn six_turns_get_back_to_the_start() {
+        let mut bearing = HexBearing::NE;
+        let mut seen = vec![];
+        for _ in 0..6 {
+            bearing = bearing.rotate(&Rotation::R);
+            seen.push(bearing);
+        }
+        use HexBearing::*;
+        assert_eq!(seen, vec![E, SE, SW, W, NW, NE]);
+
+        for _ in 0..6 {
+            bearing = bearing.rotate(&Rotation::L);
+        }
+        assert_eq!(bearing, NE);
+        assert_eq!(E.reverse(), W);
+    }
+
+    #[test]
+    fn forward_crosses_the_edge_that_the_robot_faces() -> Result<()> {
+        let grid: Grid = "4 4".parse()?;
+        let moves = [
+            ("2 2 NE", "2 3 NE"),
+            ("2 2 E", "3 2 E"),
+            ("2 2 SE", "3 1 SE"),
+            ("2 2 SW", "2 1 SW"),
+            ("2 2 W", "1 2 W"),
+            ("2 2 NW", "1 3 NW"),
+        ];
+        for (start, end) in moves.iter() {
+            let robot: HexRobot = start.parse()?;
+            let moved = robot.try_next_instruction(&grid, &Instruction::F);
+            assert_eq!(moved.map(|r| r.to_string()), Ok(end.to_string()));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn lost_robots_leave_scents() -> Result<()> {
+        let mut grid: Grid = "4 4".parse()?;
+        let robot: HexRobot = "4 0 SE".parse()?;
+        let lost = robot.clone().try_next_instruction(&grid, &Instruction::F);
+        assert_eq!(lost, Err(robot.clone()));
+
+        grid.leave_scent(&robot.coords, robot.bearing.heading());
+        let saved = robot.clone().try_next_instruction(&grid, &Instruction::F);
+        assert_eq!(saved, Ok(robot));
+        Ok(())
+    }
+
+    #[test]
+    fn scents_follow_the_grids_policy() -> Result<()> {
+        let mut grid = "4 4"
+            .parse::<Grid>()?
+            .with_scent_policy(PerCellAndBearing::default());
+        let east: HexRobot = "4 0 E".parse()?;
+        let south_east: HexRobot = "4 0 SE".parse()?;
+        grid.leave_scent(&east.coords, east.bearing.heading());
+
+        let saved = east.clone().try_next_instruction(&grid, &Instruction::F);
+        assert_eq!(saved, Ok(east));
+        let lost = south_east
+            .clone()
+            .try_next_instruction(&grid, &Instruction::F);
+        assert_eq!(lost, Err(south_east));
+        Ok(())
+    }
+}
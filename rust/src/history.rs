@@ -0,0 +1,91 @@
+//! Stepping backwards and forwards through a robot's moves, for debuggers.
+
+use crate::{trace_robot, Grid, Instruction, Robot};
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+/// Every state that a robot went through, with a cursor that can be moved
+/// in either direction. The states are recorded once, up front, so moving
+/// the cursor never re-runs the simulation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RobotHistory {
+    /// The start, followed by the robot after each instruction.
+    states: Vec<Robot>,
+    position: usize,
+}
+
+impl RobotHistory {
+    /// Records `robot` following `instructions`, with the cursor at the
+    /// start. Like trace_robot(), a lost robot stays where it was before it
+    /// fell off.
+    pub fn record(grid: &Grid, robot: Robot, instructions: &[Instruction]) -> RobotHistory {
+        let mut states = Vec::with_capacity(instructions.len() + 1);
+        states.push(robot.clone());
+        states.extend(trace_robot(grid, robot, instructions));
+        RobotHistory {
+            states,
+            position: 0,
+        }
+    }
+
+    /// The state under the cursor.
+    pub fn current(&self) -> &Robot {
+        &self.states[self.position]
+    }
+
+    /// How many instructions have been followed to get to current().
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Moves to the state after the next instruction, unless the cursor
+    /// is already at the end.
+    pub fn step_forward(&mut self) -> Option<&Robot> {
+        if self.position + 1 >= self.states.len() {
+            return None;
+        }
+        self.position += 1;
+        Some(self.current())
+    }
+
+    /// Moves to the state before the last instruction, unless the cursor
+    /// is already at the start.
+    pub fn step_back(&mut self) -> Option<&Robot> {
+        self.position = self.position.checked_sub(1)?;
+        Some(self.current())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    #[test]
+    fn stepping_forward_then_back_returns_to_the_same_state() -> Result<()> {
+        let grid: Grid = "5 3".parse()?;
+        let start: Robot = "1 1 E".parse()?;
+        let instructions = [Instruction::F, Instruction::F];
+        let mut history = RobotHistory::record(&grid, start.clone(), &instructions);
+
+        assert_eq!(history.step_back(), None);
+        assert_eq!(
+            history.step_forward().map(Robot::to_string),
+            Some("2 1 E".into())
+        );
+        assert_eq!(
+            history.step_forward().map(Robot::to_string),
+            Some("3 1 E".into())
+        );
+        assert_eq!(history.step_forward(), None);
+        assert_eq!(history.position(), 2);
+
+        assert_eq!(
+            history.step_back().map(Robot::to_string),
+            Some("2 1 E".into())
+        );
+        assert_eq!(history.step_back(), Some(&start));
+        assert_eq!(history.current(), &start);
+        Ok(())
+    }
+}
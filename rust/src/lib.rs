@@ -0,0 +1,4371 @@
+//! A solution to the Red Badger "Martian Robots" coding challenge.
+//!
+//! The binary is a thin wrapper around [`drive_robots()`], which takes an
+//! iterator of input lines and yields one output line per robot:
+//!
+//! ```
+//! use redbadger_challenge::{drive_robots, ResultOfIteratorOfResult};
+//!
+//! let input = "5 3\n1 1 E\nRFRFRFRF\n\n# Gets lost\n3 2 N\nFRRFLLFFRRFLL\n";
+//! let lines = input.lines().map(|l| Ok(l.to_owned()));
+//!
+//! let output = drive_robots(lines)
+//!     .flatten_to_iterator()
+//!     .collect::<anyhow::Result<Vec<String>>>()?;
+//!
+//! assert_eq!(output, vec!["1 1 E", "3 3 N LOST"]);
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+//!
+//! Everything apart from the movement logic (parsing, IO and output
+//! formatting) is behind the default `std` feature. Without it, the crate
+//! is `no_std` and only needs `alloc`.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(not(feature = "std"))]
+extern crate alloc;
+
+#[cfg(feature = "std")]
+mod error;
+#[cfg(feature = "std")]
+mod flatten;
+#[cfg(feature = "std")]
+mod generate;
+pub mod hex;
+mod history;
+#[cfg(feature = "parallel")]
+mod parallel;
+#[cfg(feature = "std")]
+mod render;
+#[cfg(feature = "serde")]
+mod scenario;
+mod scent;
+#[cfg(feature = "async")]
+mod stream;
+#[cfg(feature = "json")]
+pub mod wasm;
+
+#[cfg(feature = "std")]
+pub use crate::error::{LineError, ParseError, RobotError};
+#[cfg(feature = "std")]
+pub use crate::flatten::{FlattenedIteratorOfResult, ResultOfIteratorOfResult};
+#[cfg(feature = "std")]
+pub use crate::generate::{generate, mutate_instructions};
+pub use crate::history::RobotHistory;
+#[cfg(feature = "parallel")]
+pub use crate::parallel::drive_robots_parallel;
+#[cfg(feature = "std")]
+pub use crate::render::render_grid;
+#[cfg(feature = "serde")]
+pub use crate::scenario::{simulate_scenario, Scenario, ScenarioRobot};
+pub use crate::scent::{
+    scent_diff, Fading, Heading, NoScents, PerCell, PerCellAndBearing, ScentPolicy,
+};
+#[cfg(feature = "async")]
+pub use crate::stream::drive_robots_stream;
+
+#[cfg(not(feature = "std"))]
+use alloc::{boxed::Box, collections::BTreeSet as Set, string::String, vec::Vec};
+#[cfg(feature = "std")]
+use anyhow::{bail, Error, Result};
+use core::fmt::Display;
+#[cfg(feature = "std")]
+use enum_display_derive::Display;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+#[cfg(feature = "std")]
+use std::collections::HashSet as Set;
+#[cfg(feature = "std")]
+use std::convert::{TryFrom, TryInto};
+#[cfg(feature = "std")]
+use std::io::BufRead;
+#[cfg(feature = "std")]
+use std::iter::Peekable;
+#[cfg(feature = "std")]
+use std::str::{Chars, FromStr};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "std", derive(Display))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Bearing {
+    N,
+    NE,
+    E,
+    SE,
+    S,
+    SW,
+    W,
+    NW,
+}
+
+#[cfg(feature = "std")]
+impl FromStr for Bearing {
+    type Err = ParseError;
+
+    /// Accepts the abbreviations in any case, and the four main compass
+    /// points as words, like `North`.
+    fn from_str(input: &str) -> Result<Self, ParseError> {
+        use Bearing::*;
+        match input.to_ascii_uppercase().as_str() {
+            "N" | "NORTH" => Ok(N),
+            "NE" => Ok(NE),
+            "E" | "EAST" => Ok(E),
+            "SE" => Ok(SE),
+            "S" | "SOUTH" => Ok(S),
+            "SW" => Ok(SW),
+            "W" | "WEST" => Ok(W),
+            "NW" => Ok(NW),
+            _ => Err(ParseError::BadBearing),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl TryFrom<&str> for Bearing {
+    type Error = ParseError;
+
+    fn try_from(input: &str) -> Result<Self, Self::Error> {
+        input.parse()
+    }
+}
+
+impl Bearing {
+    /// An arrow pointing this way, with North up, for display only. Both
+    /// `arrows` output and render_grid() use this.
+    pub fn arrow(self) -> char {
+        use Bearing::*;
+        match self {
+            N => '↑',
+            NE => '↗',
+            E => '→',
+            SE => '↘',
+            S => '↓',
+            SW => '↙',
+            W => '←',
+            NW => '↖',
+        }
+    }
+
+    /// Which way this is, for scents.
+    pub fn heading(self) -> Heading {
+        use Bearing::*;
+        match self {
+            N => 0,
+            NE => 45,
+            E => 90,
+            SE => 135,
+            S => 180,
+            SW => 225,
+            W => 270,
+            NW => 315,
+        }
+    }
+
+    /// The opposite compass point.
+    pub fn reverse(self) -> Bearing {
+        self.rotated_by(2)
+    }
+
+    /// Turns 45 degrees, to the next of the eight compass points. This is
+    /// what L and R do on an eight_way grid.
+    pub fn rotate(self, rotation: &Rotation) -> Bearing {
+        use Bearing::*;
+        use Rotation::*;
+
+        match (self, rotation) {
+            (N, L) => NW,
+            (NE, L) => N,
+            (E, L) => NE,
+            (SE, L) => E,
+            (S, L) => SE,
+            (SW, L) => S,
+            (W, L) => SW,
+            (NW, L) => W,
+            (NW, R) => N,
+            (N, R) => NE,
+            (NE, R) => E,
+            (E, R) => SE,
+            (SE, R) => S,
+            (S, R) => SW,
+            (SW, R) => W,
+            (W, R) => NW,
+        }
+    }
+
+    /// Turns 90 degrees, which is what L and R do on every other grid (so
+    /// that the challenge's example input keeps giving the challenge's
+    /// example output). A robot facing diagonally will only ever face
+    /// diagonally.
+    pub fn quarter_turn(self, rotation: &Rotation) -> Bearing {
+        self.rotate(rotation).rotate(rotation)
+    }
+
+    /// The same as `quarter_turns` quarter turns to the right, or to the
+    /// left if it is negative. Four turns get back to the start, so only
+    /// the remainder is applied.
+    pub fn rotated_by(self, quarter_turns: i32) -> Bearing {
+        self.rotated_by_eighths(quarter_turns.rem_euclid(4) * 2)
+    }
+
+    /// Like rotated_by(), but in 45 degree turns.
+    fn rotated_by_eighths(self, eighths: i32) -> Bearing {
+        (0..eighths.rem_euclid(8)).fold(self, |bearing, _| bearing.rotate(&Rotation::R))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Rotation {
+    L,
+    R,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Instruction {
+    F,
+    B,
+    Turn(Rotation),
+    /// U-turn, without moving.
+    U,
+    /// Jump straight to {x, y}, keeping the same bearing.
+    Teleport(i32, i32),
+    /// The same as that many F instructions.
+    Forward(i32),
+    /// Leave a scent on the current cell, without moving, as if a robot
+    /// had been lost from it. Only drive_robots() and friends do this:
+    /// try_next_instruction() can't change the grid, so it ignores it.
+    Scent,
+    /// Ignore the rest of the instructions, and stop here. Functions that
+    /// follow a list of instructions stop at it, and
+    /// try_next_instruction() ignores it.
+    Halt,
+}
+
+/// The challenge says that instruction strings must be shorter than this.
+pub const MAX_INSTRUCTIONS: usize = 100;
+
+#[cfg(feature = "std")]
+impl TryFrom<char> for Instruction {
+    type Error = ParseError;
+
+    fn try_from(value: char) -> Result<Self, Self::Error> {
+        use Instruction::*;
+        use Rotation::*;
+        match value.to_ascii_uppercase() {
+            'F' => Ok(F),
+            'B' => Ok(B),
+            'L' => Ok(Turn(L)),
+            'R' => Ok(Turn(R)),
+            'U' => Ok(U),
+            'S' => Ok(Scent),
+            'H' => Ok(Halt),
+            _ => Err(ParseError::BadInstruction),
+        }
+    }
+}
+
+/// Parses an instruction line, where each instruction may be prefixed by
+/// a repeat count, written after a `*` (so "*2R*3F" means "RRFFF"). A
+/// distance after an F moves that far in one instruction, so "F3" is
+/// Forward(3), and degrees after an L or R turn that far, so "R180" is
+/// "RR". Commas and spaces between instructions are ignored, so
+/// "F, R, *2F" is the same as "FR*2F".
+#[cfg(feature = "std")]
+fn parse_instructions(line: &str) -> Result<Vec<Instruction>> {
+    parse_instructions_with_macros(line, &Macros::new())
+}
+
+/// Instruction sequences from `define NAME SEQUENCE` lines, by name.
+#[cfg(feature = "std")]
+type Macros = HashMap<String, Vec<Instruction>>;
+
+/// Can go between instructions, for readability.
+#[cfg(feature = "std")]
+fn is_separator(c: char) -> bool {
+    c == ',' || c.is_whitespace()
+}
+
+/// Letters that mean something in an instruction line, so they can't start
+/// a macro name.
+#[cfg(feature = "std")]
+const INSTRUCTION_LETTERS: &str = "FBLRUT";
+
+/// Letters that came after macros, so macro names can start with them, and
+/// a macro wins.
+#[cfg(feature = "std")]
+const LATER_INSTRUCTION_LETTERS: &str = "SH";
+
+/// Like parse_instructions(), but with the names in `macros` standing for
+/// their sequences. A repeat count before a name repeats the whole
+/// sequence.
+#[cfg(feature = "std")]
+fn parse_instructions_with_macros(line: &str, macros: &Macros) -> Result<Vec<Instruction>> {
+    let mut instructions = vec![];
+    let mut count: Option<usize> = None;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '*' && count.is_none() {
+            let digits = take_digits(&mut chars);
+            if digits.is_empty() {
+                bail!("repeat count must be written like *3F");
+            }
+            count = Some(
+                digits
+                    .parse()
+                    .map_err(|_| Error::msg("repeat count is too large"))?,
+            );
+            continue;
+        }
+        if c.is_ascii_digit() {
+            bail!("repeat count must be written like *3F");
+        }
+        if is_separator(c) || c == '*' {
+            if count.is_some() {
+                bail!("repeat count must be followed by an instruction");
+            }
+            continue;
+        }
+        let mut repeat = count.take().unwrap_or(1);
+        let instruction = match c {
+            'T' | 't' => {
+                let x = parse_teleport_coordinate(&mut chars)?;
+                if chars.next() != Some(',') {
+                    bail!("teleport must be written as Tx,y");
+                }
+                let y = parse_teleport_coordinate(&mut chars)?;
+                Instruction::Teleport(x, y)
+            }
+            'F' | 'f' if chars.peek().is_some_and(char::is_ascii_digit) => {
+                let distance = take_digits(&mut chars)
+                    .parse()
+                    .map_err(|_| Error::msg("distance is too large"))?;
+                Instruction::Forward(distance)
+            }
+            'L' | 'l' | 'R' | 'r' if chars.peek().is_some_and(char::is_ascii_digit) => {
+                let degrees: usize = take_digits(&mut chars)
+                    .parse()
+                    .map_err(|_| Error::msg("rotation is too large"))?;
+                if !degrees.is_multiple_of(90) {
+                    bail!("rotation must be a multiple of 90 degrees");
+                }
+                repeat = repeat
+                    .checked_mul(degrees / 90)
+                    .ok_or_else(|| Error::msg("repeat count is too large"))?;
+                c.try_into()?
+            }
+            c if c.is_ascii_uppercase() && !INSTRUCTION_LETTERS.contains(c) => {
+                let mut word = c.to_string();
+                word.extend(chars.clone().take_while(char::is_ascii_uppercase));
+                let name = macros
+                    .keys()
+                    .filter(|name| word.starts_with(name.as_str()))
+                    .max_by_key(|name| name.len());
+                match name {
+                    Some(name) => {
+                        chars.nth(name.len() - 2);
+                        for _ in 0..repeat {
+                            instructions.extend(macros[name].iter().cloned());
+                        }
+                        continue;
+                    }
+                    // Probably a typo, rather than one bad letter.
+                    None if word[1..].starts_with(|c| {
+                        !INSTRUCTION_LETTERS.contains(c) && !LATER_INSTRUCTION_LETTERS.contains(c)
+                    }) =>
+                    {
+                        bail!("unknown macro {}", word)
+                    }
+                    None => c.try_into()?,
+                }
+            }
+            c => c.try_into()?,
+        };
+        for _ in 0..repeat {
+            instructions.push(instruction.clone());
+        }
+    }
+    if count.is_some() {
+        bail!("repeat count must be followed by an instruction");
+    }
+    Ok(instructions)
+}
+
+#[cfg(feature = "std")]
+fn take_digits(chars: &mut Peekable<Chars>) -> String {
+    let mut digits = String::new();
+    while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+        digits.push(digit);
+    }
+    digits
+}
+
+#[cfg(feature = "std")]
+fn parse_teleport_coordinate(chars: &mut Peekable<Chars>) -> Result<i32> {
+    let mut number = String::new();
+    if chars.peek() == Some(&'-') {
+        number.extend(chars.next());
+    }
+    number.push_str(&take_digits(chars));
+    number
+        .parse()
+        .map_err(|_| Error::msg("teleport must be written as Tx,y"))
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Coords {
+    pub x: i32,
+    pub y: i32,
+}
+
+#[cfg(feature = "std")]
+impl<'a> Coords {
+    fn try_from_iterator(split: &mut impl Iterator<Item = &'a str>) -> Result<Self, ParseError> {
+        let x = split.next().ok_or(ParseError::MissingX)?;
+        let y = split.next().ok_or(ParseError::MissingY)?;
+        Ok(Coords {
+            x: parse_number(x)?,
+            y: parse_number(y)?,
+        })
+    }
+
+    /// Expects `width=x height=y`, in either order.
+    fn try_from_keywords(size_line: &str) -> Result<Self, anyhow::Error> {
+        let (mut width, mut height) = (None, None);
+        for field in size_line.split_whitespace() {
+            let (key, value) = field
+                .split_once('=')
+                .ok_or_else(|| Error::msg("grid line must be all numbers or all key=value"))?;
+            let slot = match key {
+                "width" => &mut width,
+                "height" => &mut height,
+                _ => bail!("unknown grid key {}", key),
+            };
+            if slot.replace(parse_number(value)?).is_some() {
+                bail!("grid key {} is given twice", key);
+            }
+        }
+        let x = width.ok_or(ParseError::MissingX)?;
+        let y = height.ok_or(ParseError::MissingY)?;
+        Ok(Coords { x, y })
+    }
+}
+
+#[cfg(feature = "std")]
+fn parse_number(field: &str) -> Result<i32, ParseError> {
+    field.parse().map_err(|_| ParseError::InvalidNumber)
+}
+
+/// The challenge says that no grid coordinate may be larger than this.
+pub const MAX_COORDINATE: i32 = 50;
+
+// The grid looks like this:
+//     y (North)
+//     ^
+//     |
+//     +-------> x (East)
+// with min in the bottom left corner and max in the top right. min is
+// {0, 0} unless the grid line gives all four coordinates.
+// If a robot falls off the edge then we add {x, y} to scents, unless
+// wrap is set, in which case it comes back on at the opposite edge.
+// If ghost is set then robots carry on off the edge instead, so nobody is
+// ever lost and there are never any scents.
+// If robots are colliding then we add where each one parks to occupied.
+// Robots can't drive into obstacles or occupied cells, but they don't get
+// lost by trying.
+// If y_down is set then North is towards min.y instead, like on a screen.
+// If eight_way is set then L and R turn 45 degrees instead of 90, so robots
+// can turn to face diagonally.
+// Each F or B moves stride cells, and only the cell that the robot lands on
+// matters: it can jump over obstacles, other robots and scented cells.
+// Grids deserialize from the same string as the grid line.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Deserialize), serde(try_from = "String"))]
+pub struct Grid {
+    min: Coords,
+    max: Coords,
+    scents: Box<dyn ScentPolicy>,
+    occupied: Set<Coords>,
+    obstacles: Set<Coords>,
+    wrap: bool,
+    y_down: bool,
+    stride: i32,
+    ghost: bool,
+    eight_way: bool,
+}
+
+#[cfg(feature = "std")]
+impl FromStr for Grid {
+    type Err = anyhow::Error;
+
+    /// Expects either `max_x max_y` or `min_x min_y max_x max_y`.
+    /// `width=max_x height=max_y` is the same as `max_x max_y`.
+    fn from_str(size_line: &str) -> Result<Self> {
+        let size_line = size_line.trim();
+        if size_line.is_empty() {
+            return Err(ParseError::BlankLine("grid").into());
+        }
+        let mut split = size_line.split_whitespace();
+        let keywords = size_line.contains('=');
+        let (min, max) = match size_line.split_whitespace().count() {
+            4 if !keywords => {
+                let min = Coords::try_from_iterator(&mut split)?;
+                let max = Coords::try_from_iterator(&mut split)?;
+                if min.x < -MAX_COORDINATE || min.y < -MAX_COORDINATE {
+                    return Err(ParseError::CoordinateTooLarge.into());
+                }
+                if min.x > max.x || min.y > max.y {
+                    return Err(ParseError::MinAboveMax.into());
+                }
+                (min, max)
+            }
+            3 | 5.. => return Err(ParseError::TooManyFields("grid").into()),
+            _ => {
+                let max = if keywords {
+                    Coords::try_from_keywords(size_line)?
+                } else {
+                    Coords::try_from_iterator(&mut split)?
+                };
+                if max.x < 0 || max.y < 0 {
+                    return Err(ParseError::NegativeCoordinate.into());
+                }
+                (Coords::default(), max)
+            }
+        };
+        if max.x > MAX_COORDINATE || max.y > MAX_COORDINATE {
+            return Err(ParseError::CoordinateTooLarge.into());
+        }
+        Ok(Grid::with_bounds(min, max))
+    }
+}
+
+/// The same format as the grid line in the input.
+impl Display for Grid {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        if self.min != Coords::default() {
+            write!(f, "{} {} ", self.min.x, self.min.y)?;
+        }
+        write!(f, "{} {}", self.max.x, self.max.y)
+    }
+}
+
+#[cfg(feature = "std")]
+impl TryFrom<&str> for Grid {
+    type Error = anyhow::Error;
+
+    fn try_from(size_line: &str) -> Result<Self, Self::Error> {
+        size_line.parse()
+    }
+}
+
+#[cfg(feature = "std")]
+impl TryFrom<String> for Grid {
+    type Error = anyhow::Error;
+
+    fn try_from(size_line: String) -> Result<Self, Self::Error> {
+        size_line.as_str().try_into()
+    }
+}
+
+/// The grid from the line `0 0`, which is the single cell {0, 0}, with no
+/// scents and the challenge's rules.
+impl Default for Grid {
+    fn default() -> Self {
+        Grid::new(0, 0)
+    }
+}
+
+impl Grid {
+    /// An empty grid from {0, 0} to {x_max, y_max}, like the grid line
+    /// `x_max y_max`. This does no validation, so it is up to the caller to
+    /// keep both small and non-negative.
+    pub fn new(x_max: i32, y_max: i32) -> Grid {
+        Grid::with_bounds(Coords::default(), Coords { x: x_max, y: y_max })
+    }
+
+    /// An empty grid from `min` to `max`. Like new(), this does no
+    /// validation.
+    pub fn with_bounds(min: Coords, max: Coords) -> Grid {
+        Grid {
+            min,
+            max,
+            scents: Box::new(PerCell::default()),
+            occupied: Default::default(),
+            obstacles: Default::default(),
+            wrap: false,
+            y_down: false,
+            stride: 1,
+            ghost: false,
+            eight_way: false,
+        }
+    }
+
+    /// Whether {x, y} is on the grid.
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        self.min.x <= x && x <= self.max.x && self.min.y <= y && y <= self.max.y
+    }
+
+    /// The cell on the grid that is nearest to {x, y}, clamping each
+    /// coordinate separately.
+    pub fn clamp(&self, x: i32, y: i32) -> (i32, i32) {
+        (
+            x.clamp(self.min.x, self.max.x),
+            y.clamp(self.min.y, self.max.y),
+        )
+    }
+
+    fn wrapped(&self, coords: Coords) -> Coords {
+        // In i64, so that a robot far off a huge grid can't overflow.
+        let wrap = |n: i32, min: i32, max: i32| {
+            let size = i64::from(max) - i64::from(min) + 1;
+            ((i64::from(n) - i64::from(min)).rem_euclid(size) + i64::from(min)) as i32
+        };
+        Coords {
+            x: wrap(coords.x, self.min.x, self.max.x),
+            y: wrap(coords.y, self.min.y, self.max.y),
+        }
+    }
+
+    /// How many F instructions `robot` can follow before the next one
+    /// would take it off the grid. Scents, obstacles and other robots are
+    /// ignored.
+    pub fn safe_forward_steps(&self, robot: &Robot) -> u32 {
+        use Bearing::*;
+        let Coords { x, y } = robot.coords;
+        let (north, south) = if self.y_down {
+            (y.saturating_sub(self.min.y), self.max.y.saturating_sub(y))
+        } else {
+            (self.max.y.saturating_sub(y), y.saturating_sub(self.min.y))
+        };
+        let east = self.max.x.saturating_sub(x);
+        let west = x.saturating_sub(self.min.x);
+        let steps = match robot.bearing {
+            N => north,
+            NE => north.min(east),
+            E => east,
+            SE => south.min(east),
+            S => south,
+            SW => south.min(west),
+            W => west,
+            NW => north.min(west),
+        };
+        (steps.max(0) / self.stride.max(1)) as u32
+    }
+
+    /// How many cells the grid has. A grid line of `5 3` means 6 by 4.
+    pub fn area(&self) -> i64 {
+        let width = i64::from(self.max.x) - i64::from(self.min.x) + 1;
+        let height = i64::from(self.max.y) - i64::from(self.min.y) + 1;
+        width * height
+    }
+
+    /// The fraction of cells that a robot has been lost from.
+    pub fn scent_density(&self) -> f64 {
+        self.scents.cells().len() as f64 / self.area() as f64
+    }
+
+    /// Adds the scents from `other`, e.g. from a simulation that ran on a
+    /// copy of this grid. They are added under this grid's ScentPolicy,
+    /// even if they are outside it or the grids are different sizes.
+    pub fn merge_scents(&mut self, other: &Grid) {
+        for (coords, heading) in other.scents.lost_from() {
+            self.scents.apply_scent(&coords, heading);
+        }
+    }
+
+    /// Forgets every scent, so that the same grid can be used for another
+    /// run. Obstacles and parked robots stay.
+    pub fn clear_scents(&mut self) {
+        self.scents.clear();
+    }
+
+    /// Everywhere that a robot has fallen off, sorted by x and then y.
+    pub fn scents(&self) -> Vec<Coords> {
+        let mut scents = self.scents.cells();
+        scents.sort();
+        scents
+    }
+
+    /// Makes each F or B move `stride` cells rather than one. `stride`
+    /// should be positive.
+    pub fn with_stride(mut self, stride: i32) -> Grid {
+        self.stride = stride;
+        self
+    }
+
+    /// Replaces the rule for which robots are saved by scents. The default
+    /// is PerCell, which is the rule from the challenge. Any scents that
+    /// have already been left are forgotten.
+    pub fn with_scent_policy(mut self, policy: impl ScentPolicy + 'static) -> Grid {
+        self.scents = Box::new(policy);
+        self
+    }
+
+    fn has_scent(&self, robot: &Robot) -> bool {
+        self.scents
+            .has_scent(&robot.coords, robot.bearing.heading())
+    }
+
+    /// Like Robot::try_next_instruction(), but a scent that saves the robot
+    /// is used up, which matters for Fading scents. Everything else only
+    /// asks what would happen, so this is just for Simulation::drive().
+    #[cfg(feature = "std")]
+    fn move_robot(
+        &mut self,
+        robot: Robot,
+        instruction: &Instruction,
+    ) -> core::result::Result<Robot, Robot> {
+        match instruction {
+            Instruction::Forward(steps) => {
+                let mut current = robot;
+                for _ in 0..*steps {
+                    let next = self.move_robot(current.clone(), &Instruction::F)?;
+                    if next == current {
+                        break;
+                    }
+                    current = next;
+                }
+                Ok(current)
+            }
+            Instruction::F | Instruction::B | Instruction::Teleport(..) => {
+                let next = robot.destination(self, instruction);
+                let falls_off = robot.falls_off(self, &next);
+                let result = robot.try_moving_to(self, next);
+                if let (true, Ok(saved)) = (falls_off, &result) {
+                    self.scents
+                        .use_scent(&saved.coords, saved.bearing.heading());
+                }
+                result
+            }
+            _ => robot.try_next_instruction(self, instruction),
+        }
+    }
+
+    /// Whether a robot has been lost from {x, y}. Like every other query,
+    /// this never uses up a Fading scent.
+    pub fn has_scent_at(&self, x: i32, y: i32) -> bool {
+        self.scents.is_scented(&Coords { x, y })
+    }
+
+    /// Marks where `robot` fell off, so that later robots don't. Callers
+    /// that drive robots themselves need to do this for each lost robot.
+    pub fn apply_scent(&mut self, robot: &Robot) {
+        self.leave_scent(&robot.coords, robot.bearing.heading());
+    }
+
+    /// Like apply_scent(), for robots that aren't Robots, like HexRobots.
+    pub fn leave_scent(&mut self, coords: &Coords, heading: Heading) {
+        self.scents.apply_scent(coords, heading);
+    }
+
+    fn is_occupied(&self, robot: &Robot) -> bool {
+        self.occupied.contains(&robot.coords)
+    }
+
+    /// Parks `robot`, so that later robots can't drive into it.
+    pub fn occupy(&mut self, robot: &Robot) {
+        self.occupied.insert(robot.coords.clone());
+    }
+
+    fn is_obstacle(&self, robot: &Robot) -> bool {
+        self.obstacles.contains(&robot.coords)
+    }
+
+    /// Expects a line like `# 2 2`.
+    #[cfg(feature = "std")]
+    fn add_obstacle(&mut self, obstacle_line: &str) -> Result<()> {
+        let mut split = obstacle_line.split_whitespace().skip(1);
+        let coords = Coords::try_from_iterator(&mut split)?;
+        if split.next().is_some() {
+            return Err(ParseError::TooManyFields("obstacle").into());
+        }
+        if !self.contains(coords.x, coords.y) {
+            bail!("obstacle is outside the grid");
+        }
+        self.obstacles.insert(coords);
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Robot {
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    pub coords: Coords,
+    pub bearing: Bearing,
+    /// The name from an `@name` line before the position line, if any.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub label: Option<String>,
+}
+
+#[cfg(feature = "std")]
+impl FromStr for Robot {
+    type Err = anyhow::Error;
+
+    fn from_str(position_line: &str) -> Result<Self> {
+        let position_line = position_line.trim();
+        let mut split = position_line.split_whitespace();
+        let coords = Coords::try_from_iterator(&mut split)?;
+        let bearing = split.next().ok_or(ParseError::MissingBearing)?.parse()?;
+        if split.next().is_some() {
+            return Err(ParseError::TooManyFields("position").into());
+        }
+        Ok(Robot {
+            coords,
+            bearing,
+            label: None,
+        })
+    }
+}
+
+/// The same format as a position line in the input.
+#[cfg(feature = "std")]
+impl Display for Robot {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} {} {}", self.coords.x, self.coords.y, self.bearing)
+    }
+}
+
+#[cfg(feature = "std")]
+impl TryFrom<&str> for Robot {
+    type Error = anyhow::Error;
+
+    fn try_from(position_line: &str) -> Result<Self, Self::Error> {
+        position_line.parse()
+    }
+}
+
+#[cfg(feature = "std")]
+impl TryFrom<String> for Robot {
+    type Error = anyhow::Error;
+
+    fn try_from(position_line: String) -> Result<Self, Self::Error> {
+        position_line.as_str().try_into()
+    }
+}
+
+impl Robot {
+    fn is_out_of_bounds(&self, grid: &Grid) -> bool {
+        !grid.contains(self.coords.x, self.coords.y)
+    }
+
+    /// Moves `steps` cells along the robot's bearing (negative steps
+    /// move backwards). North is towards larger y, unless `y_down`.
+    /// Returns None if a coordinate would overflow, which is off any grid.
+    fn checked_move(mut self, steps: i32, y_down: bool) -> Option<Self> {
+        use Bearing::*;
+        let (dx, north) = match self.bearing {
+            N => (0, 1),
+            NE => (1, 1),
+            E => (1, 0),
+            SE => (1, -1),
+            S => (0, -1),
+            SW => (-1, -1),
+            W => (-1, 0),
+            NW => (-1, 1),
+        };
+        let dy = if y_down { -north } else { north };
+        self.coords.x = self.coords.x.checked_add(dx * steps)?;
+        self.coords.y = self.coords.y.checked_add(dy * steps)?;
+        Some(self)
+    }
+
+    /// Turns on the spot, like L or R on any grid that isn't eight_way.
+    pub fn turn(self, rotation: &Rotation) -> Robot {
+        Robot {
+            bearing: self.bearing.quarter_turn(rotation),
+            ..self
+        }
+    }
+
+    /// Moves forwards like an F instruction (so one cell, unless the grid
+    /// has a stride). Edges, scents, obstacles and other robots all count,
+    /// so this returns either where the robot ended up or (as Err) where
+    /// it was before it fell off.
+    pub fn advance(self, grid: &Grid) -> core::result::Result<Robot, Robot> {
+        let next = self.destination(grid, &Instruction::F);
+        self.try_moving_to(grid, next)
+    }
+
+    /// Where `instruction` would take the robot if there were no edges,
+    /// scents or other robots to worry about. None means somewhere that
+    /// doesn't fit in an i32.
+    fn destination(&self, grid: &Grid, instruction: &Instruction) -> Option<Robot> {
+        let mut next = match instruction {
+            Instruction::Turn(t) if grid.eight_way => Robot {
+                bearing: self.bearing.rotate(t),
+                ..self.clone()
+            },
+            Instruction::Turn(t) => self.clone().turn(t),
+            Instruction::U => Robot {
+                bearing: self.bearing.reverse(),
+                ..self.clone()
+            },
+            Instruction::F => self.clone().checked_move(grid.stride, grid.y_down)?,
+            Instruction::B => self.clone().checked_move(-grid.stride, grid.y_down)?,
+            Instruction::Forward(steps) => {
+                let steps = steps.checked_mul(grid.stride)?;
+                self.clone().checked_move(steps, grid.y_down)?
+            }
+            Instruction::Teleport(x, y) => Robot {
+                coords: Coords { x: *x, y: *y },
+                ..self.clone()
+            },
+            Instruction::Scent | Instruction::Halt => self.clone(),
+        };
+        if grid.wrap {
+            next.coords = grid.wrapped(next.coords);
+        }
+        Some(next)
+    }
+
+    /// Whether `instruction` would move the robot onto a cell where
+    /// another robot has already parked.
+    pub fn is_blocked(&self, grid: &Grid, instruction: &Instruction) -> bool {
+        if let Instruction::Forward(steps) = instruction {
+            let mut current = self.clone();
+            for _ in 0..*steps {
+                if current.is_blocked(grid, &Instruction::F) {
+                    return true;
+                }
+                // A robot that doesn't move won't be blocked by the rest
+                // either.
+                match current.clone().advance(grid) {
+                    Ok(next) if next != current => current = next,
+                    _ => return false,
+                }
+            }
+            return false;
+        }
+        let next = self.destination(grid, instruction);
+        matches!(next, Some(next) if next.coords != self.coords && grid.is_occupied(&next))
+    }
+
+    /// Whether moving to `next` would take the robot off the grid, unless a
+    /// scent saves it.
+    fn falls_off(&self, grid: &Grid, next: &Option<Robot>) -> bool {
+        match next {
+            Some(next) if grid.is_occupied(next) || grid.is_obstacle(next) => false,
+            Some(next) => !grid.ghost && next.is_out_of_bounds(grid),
+            None => true,
+        }
+    }
+
+    fn try_moving_to(self, grid: &Grid, next: Option<Robot>) -> core::result::Result<Robot, Robot> {
+        match next {
+            _ if self.falls_off(grid, &next) && grid.has_scent(&self) => Ok(self),
+            _ if self.falls_off(grid, &next) => Err(self),
+            Some(next) if !grid.is_occupied(&next) && !grid.is_obstacle(&next) => Ok(next),
+            _ => Ok(self),
+        }
+    }
+
+    /// Returns either the position that the robot ended up at or the
+    /// position where it was before it fell off the board.
+    pub fn try_next_instruction(
+        self,
+        grid: &Grid,
+        instruction: &Instruction,
+    ) -> core::result::Result<Robot, Robot> {
+        if let Instruction::Forward(steps) = instruction {
+            // One cell at a time, so that scents are checked on the way. A
+            // robot that stops moving (at a scent or an obstacle) stays put.
+            let mut current = self;
+            for _ in 0..*steps {
+                let next = current.clone().advance(grid)?;
+                if next == current {
+                    break;
+                }
+                current = next;
+            }
+            return Ok(current);
+        }
+        if let Instruction::F = instruction {
+            return self.advance(grid);
+        }
+        let next = self.destination(grid, instruction);
+        match instruction {
+            Instruction::Turn(_) | Instruction::U | Instruction::Scent | Instruction::Halt => {
+                Ok(next.unwrap_or(self))
+            }
+            Instruction::B | Instruction::Teleport(..) => self.try_moving_to(grid, next),
+            Instruction::F | Instruction::Forward(_) => unreachable!(),
+        }
+    }
+
+    /// Returns either the position that the robot ended up at or the
+    /// position where it was before it fell off the board.
+    pub fn try_all_instructions(
+        self,
+        grid: &Grid,
+        instructions: &[Instruction],
+    ) -> core::result::Result<Robot, Robot> {
+        let instructions = until_halt(instructions);
+        if let Some(eighths) = net_turn(grid, instructions) {
+            return Ok(Robot {
+                bearing: self.bearing.rotated_by_eighths(eighths),
+                ..self
+            });
+        }
+        let mut current = self;
+        for instruction in instructions {
+            current = current.try_next_instruction(grid, instruction)?;
+        }
+        Ok(current)
+    }
+}
+
+/// The instructions before the first Halt, or all of them if there isn't
+/// one.
+pub(crate) fn until_halt(instructions: &[Instruction]) -> &[Instruction] {
+    let end = instructions
+        .iter()
+        .position(|instruction| *instruction == Instruction::Halt);
+    &instructions[..end.unwrap_or(instructions.len())]
+}
+
+/// How far `instructions` turn a robot on `grid`, in 45 degree turns to
+/// the right (modulo 8), or None if any of them do more than turn.
+fn net_turn(grid: &Grid, instructions: &[Instruction]) -> Option<i32> {
+    let step = if grid.eight_way { 1 } else { 2 };
+    instructions.iter().try_fold(0, |turns, instruction| {
+        let turn = match instruction {
+            Instruction::Turn(Rotation::R) => step,
+            Instruction::Turn(Rotation::L) => 8 - step,
+            Instruction::U => 4,
+            _ => return None,
+        };
+        Some((turns + turn) % 8)
+    })
+}
+
+/// Returns the robot after each instruction. Once the robot is lost (or
+/// halted) it stays where it was before it fell off, so the trace is always
+/// as long as `instructions`.
+pub fn trace_robot(grid: &Grid, robot: Robot, instructions: &[Instruction]) -> Vec<Robot> {
+    let mut trace = Vec::with_capacity(instructions.len());
+    let mut current = Ok(robot);
+    for instruction in until_halt(instructions) {
+        current = current.and_then(|robot| robot.try_next_instruction(grid, instruction));
+        trace.push(match &current {
+            Ok(robot) | Err(robot) => robot.clone(),
+        });
+    }
+    let (Ok(last) | Err(last)) = current;
+    trace.resize(instructions.len(), last);
+    trace
+}
+
+/// Whether `robot` would fall off `grid`, given the scents that are already
+/// there. The grid is not changed, so this can be asked as often as you
+/// like. try_all_instructions() gives the final position as well.
+pub fn will_be_lost(grid: &Grid, robot: &Robot, instructions: &[Instruction]) -> bool {
+    robot
+        .clone()
+        .try_all_instructions(grid, instructions)
+        .is_err()
+}
+
+/// What analyze_instructions() found.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Analysis {
+    /// The robot stays on the grid for all of the instructions.
+    pub safe: bool,
+    /// The index of the instruction that takes the robot off the grid.
+    pub lost_at_step: Option<usize>,
+}
+
+/// Like will_be_lost(), but also says which instruction would be the
+/// robot's last. Scents that are already on `grid` count, and the grid is
+/// not changed.
+pub fn analyze_instructions(grid: &Grid, robot: Robot, instructions: &[Instruction]) -> Analysis {
+    let mut current = robot;
+    for (step, instruction) in until_halt(instructions).iter().enumerate() {
+        match current.try_next_instruction(grid, instruction) {
+            Ok(next) => current = next,
+            Err(_) => {
+                return Analysis {
+                    safe: false,
+                    lost_at_step: Some(step),
+                }
+            }
+        }
+    }
+    Analysis {
+        safe: true,
+        lost_at_step: None,
+    }
+}
+
+/// Where a robot ended up. If it is lost then `robot` is the last position
+/// it had before it fell off the grid.
+#[cfg(feature = "std")]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "json", derive(Serialize))]
+pub struct RobotOutcome {
+    #[cfg_attr(feature = "json", serde(flatten))]
+    pub robot: Robot,
+    pub lost: bool,
+    /// Set if the robot tried to drive into another robot at some point.
+    #[cfg_attr(feature = "json", serde(skip_serializing_if = "std::ops::Not::not"))]
+    pub blocked: bool,
+    /// Every step that the robot took, if `SimulationConfig::trace` is set.
+    #[cfg_attr(feature = "json", serde(skip_serializing_if = "Vec::is_empty"))]
+    pub trace: Vec<Robot>,
+    /// Where the robot started.
+    #[cfg_attr(feature = "json", serde(skip))]
+    pub start: Robot,
+    /// How many instructions the robot was given.
+    #[cfg_attr(feature = "json", serde(skip))]
+    pub instruction_count: usize,
+    /// How many of them it followed before it fell off. This is all of
+    /// them unless it is lost, or it reached an H.
+    #[cfg_attr(feature = "json", serde(skip))]
+    pub executed: usize,
+    /// Everywhere that the robot went, including the cell that it fell
+    /// into if it is lost.
+    #[cfg_attr(feature = "json", serde(skip))]
+    pub extent: Extent,
+    /// The number of the robot's position line in the input, counting
+    /// from 1, including blank lines and comments.
+    #[cfg_attr(feature = "json", serde(skip))]
+    pub line: usize,
+    /// The robot's instructions as they were written in the input, before
+    /// any macros were expanded.
+    #[cfg_attr(feature = "json", serde(skip))]
+    pub raw_instructions: String,
+}
+
+#[cfg(feature = "std")]
+impl RobotOutcome {
+    /// The Manhattan distance from `start` to `robot`, which is where a
+    /// lost robot was reported.
+    pub fn distance(&self) -> i64 {
+        let dx = i64::from(self.robot.coords.x) - i64::from(self.start.coords.x);
+        let dy = i64::from(self.robot.coords.y) - i64::from(self.start.coords.y);
+        dx.abs() + dy.abs()
+    }
+}
+
+/// The smallest box around a set of cells.
+#[cfg(feature = "std")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Extent {
+    pub min: Coords,
+    pub max: Coords,
+}
+
+#[cfg(feature = "std")]
+impl Extent {
+    /// A box around a single cell.
+    pub fn of(coords: &Coords) -> Extent {
+        Extent {
+            min: coords.clone(),
+            max: coords.clone(),
+        }
+    }
+
+    /// Grows the box to include `coords`.
+    pub fn add(&mut self, coords: &Coords) {
+        self.min.x = self.min.x.min(coords.x);
+        self.min.y = self.min.y.min(coords.y);
+        self.max.x = self.max.x.max(coords.x);
+        self.max.y = self.max.y.max(coords.y);
+    }
+
+    /// Grows the box to include all of `other`.
+    pub fn merge(&mut self, other: &Extent) {
+        self.add(&other.min);
+        self.add(&other.max);
+    }
+}
+
+/// The same format as a four-field grid line.
+#[cfg(feature = "std")]
+impl Display for Extent {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {} {}",
+            self.min.x, self.min.y, self.max.x, self.max.y
+        )
+    }
+}
+
+/// Which position to report for a lost robot. Either way, the scent is
+/// left in the last cell that the robot was in.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LostPosition {
+    /// The last cell that the robot was in before it fell off, e.g.
+    /// `3 3 N LOST`. This is what the challenge asks for.
+    #[default]
+    LastOnGrid,
+    /// The cell off the edge of the grid that the robot tried to move
+    /// into, e.g. `3 4 N LOST`.
+    Attempted,
+}
+
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// The format from the challenge, e.g. `3 3 N LOST`.
+    #[default]
+    Text,
+    /// One JSON object per robot, e.g.
+    /// `{"x":3,"y":3,"bearing":"N","lost":true}`.
+    #[cfg(feature = "json")]
+    Json,
+}
+
+/// Formats an outcome the way that the challenge asks for, e.g. `3 3 N LOST`.
+#[cfg(feature = "std")]
+pub fn format_outcome(outcome: &RobotOutcome) -> String {
+    format_outcome_marked(outcome, DEFAULT_LOST_MARKER)
+}
+
+/// Like format_outcome(), but with something else in place of `LOST`.
+#[cfg(feature = "std")]
+pub fn format_outcome_marked(outcome: &RobotOutcome, lost_marker: &str) -> String {
+    format_outcome_with(outcome, lost_marker, false)
+}
+
+/// Like format_outcome_marked(), but with an arrow in place of the bearing
+/// if `arrows` is set, e.g. `3 3 ↑ LOST`.
+#[cfg(feature = "std")]
+fn format_outcome_with(outcome: &RobotOutcome, lost_marker: &str, arrows: bool) -> String {
+    let RobotOutcome {
+        robot,
+        lost,
+        blocked,
+        ..
+    } = outcome;
+    let position = if arrows {
+        let Coords { x, y } = robot.coords;
+        format!("{} {} {}", x, y, robot.bearing.arrow())
+    } else {
+        robot.to_string()
+    };
+    let mut line = match &robot.label {
+        Some(label) => format!("{}: {}", label, position),
+        None => position,
+    };
+    if *lost {
+        line.push(' ');
+        line.push_str(lost_marker);
+    }
+    if *blocked {
+        line.push_str(" BLOCKED");
+    }
+    line
+}
+
+#[cfg(feature = "std")]
+pub const DEFAULT_LOST_MARKER: &str = "LOST";
+
+/// Tweaks to the rules of the simulation and the way that its results are
+/// reported. The default is what the challenge asks for, which is what
+/// drive_robots() uses.
+#[cfg(feature = "std")]
+#[derive(Clone, Debug)]
+pub struct SimulationConfig {
+    /// Robots that drive off one edge of the grid reappear at the
+    /// opposite edge, so they are never lost and never leave scents.
+    pub wrap: bool,
+    /// Robots park where they finish, and later robots can't drive
+    /// through them.
+    pub collisions: bool,
+    pub format: OutputFormat,
+    /// Printed after lost robots in the text format.
+    pub lost_marker: String,
+    /// Record every step that each robot takes.
+    pub trace: bool,
+    /// Only report robots that got lost.
+    pub lost_only: bool,
+    /// Instructions can be spread over several lines, up to the next
+    /// position or grid line.
+    pub multiline_instructions: bool,
+    /// Treat a lost robot as an error, rather than as an outcome.
+    pub stop_on_lost: bool,
+    /// Warn about robots that are given no instructions.
+    pub warn_empty: bool,
+    pub lost_position: LostPosition,
+    /// Give up (with an error for each robot) after this many robots.
+    pub max_robots: Option<usize>,
+    /// North is towards smaller y, like screen coordinates.
+    pub y_down: bool,
+    /// Refuse to drive a robot whose instructions have more than this
+    /// many steps (counting `F5` as five), with an error for that robot.
+    pub max_steps: Option<usize>,
+    /// How many cells each F or B moves. See Grid::with_stride().
+    pub stride: i32,
+    /// Robots that drive off the grid carry on moving, and their positions
+    /// are reported as they are, so they are never lost.
+    pub ghost: bool,
+    /// L and R turn 45 degrees, through all eight compass points.
+    pub eight_way: bool,
+    /// Say how many of its instructions each robot followed, in the text
+    /// format, e.g. `3 3 N LOST (executed 7 of 13)`.
+    pub verbose: bool,
+    /// Print bearings as arrows, like `↑`, in the text format.
+    pub arrows: bool,
+    /// Only accept the layout from the challenge: no comments, no extra
+    /// whitespace, and no blank lines apart from one after each robot.
+    pub strict: bool,
+    /// Say how far each robot ended up from where it started, in the text
+    /// format.
+    pub distance: bool,
+    /// Start each line of the text format with the number of the robot's
+    /// position line, like `L3: 1 1 E`.
+    pub line_numbers: bool,
+    /// End each line of the text format with the instructions that the
+    /// robot was given, like `1 1 E  <= RFRFRFRF`.
+    pub echo_instructions: bool,
+    /// Each scent only saves this many robots, using Fading. None means
+    /// that scents last forever, like the challenge says.
+    pub scent_uses: Option<u32>,
+    /// The top right corner of the grid to use if the input starts with a
+    /// robot rather than a grid line.
+    pub default_grid: Option<Coords>,
+}
+
+#[cfg(feature = "std")]
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        SimulationConfig {
+            wrap: false,
+            collisions: false,
+            format: OutputFormat::default(),
+            lost_marker: DEFAULT_LOST_MARKER.to_owned(),
+            trace: false,
+            lost_only: false,
+            multiline_instructions: false,
+            stop_on_lost: false,
+            warn_empty: false,
+            lost_position: LostPosition::default(),
+            max_robots: None,
+            y_down: false,
+            max_steps: None,
+            stride: 1,
+            ghost: false,
+            eight_way: false,
+            verbose: false,
+            arrows: false,
+            strict: false,
+            distance: false,
+            line_numbers: false,
+            echo_instructions: false,
+            scent_uses: None,
+            default_grid: None,
+        }
+    }
+}
+
+/// Each field can also be set with a with_*() method, so that a config can
+/// be built up in one expression, starting from the default:
+///
+/// ```
+/// use redbadger_challenge::SimulationConfig;
+///
+/// let config = SimulationConfig::default().with_wrap(true).with_stride(2);
+/// assert!(config.wrap);
+/// ```
+#[cfg(feature = "std")]
+impl SimulationConfig {
+    /// Sets `wrap`.
+    pub fn with_wrap(mut self, wrap: bool) -> Self {
+        self.wrap = wrap;
+        self
+    }
+
+    /// Sets `collisions`.
+    pub fn with_collisions(mut self, collisions: bool) -> Self {
+        self.collisions = collisions;
+        self
+    }
+
+    /// Sets `format`.
+    pub fn with_format(mut self, format: OutputFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Sets `lost_marker`.
+    pub fn with_lost_marker(mut self, lost_marker: impl Into<String>) -> Self {
+        self.lost_marker = lost_marker.into();
+        self
+    }
+
+    /// Sets `trace`.
+    pub fn with_trace(mut self, trace: bool) -> Self {
+        self.trace = trace;
+        self
+    }
+
+    /// Sets `lost_only`.
+    pub fn with_lost_only(mut self, lost_only: bool) -> Self {
+        self.lost_only = lost_only;
+        self
+    }
+
+    /// Sets `multiline_instructions`.
+    pub fn with_multiline_instructions(mut self, multiline_instructions: bool) -> Self {
+        self.multiline_instructions = multiline_instructions;
+        self
+    }
+
+    /// Sets `stop_on_lost`.
+    pub fn with_stop_on_lost(mut self, stop_on_lost: bool) -> Self {
+        self.stop_on_lost = stop_on_lost;
+        self
+    }
+
+    /// Sets `warn_empty`.
+    pub fn with_warn_empty(mut self, warn_empty: bool) -> Self {
+        self.warn_empty = warn_empty;
+        self
+    }
+
+    /// Sets `lost_position`.
+    pub fn with_lost_position(mut self, lost_position: LostPosition) -> Self {
+        self.lost_position = lost_position;
+        self
+    }
+
+    /// Sets `max_robots`.
+    pub fn with_max_robots(mut self, max_robots: usize) -> Self {
+        self.max_robots = Some(max_robots);
+        self
+    }
+
+    /// Sets `y_down`.
+    pub fn with_y_down(mut self, y_down: bool) -> Self {
+        self.y_down = y_down;
+        self
+    }
+
+    /// Sets `max_steps`.
+    pub fn with_max_steps(mut self, max_steps: usize) -> Self {
+        self.max_steps = Some(max_steps);
+        self
+    }
+
+    /// Sets `stride`.
+    pub fn with_stride(mut self, stride: i32) -> Self {
+        self.stride = stride;
+        self
+    }
+
+    /// Sets `ghost`.
+    pub fn with_ghost(mut self, ghost: bool) -> Self {
+        self.ghost = ghost;
+        self
+    }
+
+    /// Sets `eight_way`.
+    pub fn with_eight_way(mut self, eight_way: bool) -> Self {
+        self.eight_way = eight_way;
+        self
+    }
+
+    /// Sets `verbose`.
+    pub fn with_verbose(mut self, verbose: bool) -> Self {
+        self.verbose = verbose;
+        self
+    }
+
+    /// Sets `arrows`.
+    pub fn with_arrows(mut self, arrows: bool) -> Self {
+        self.arrows = arrows;
+        self
+    }
+
+    /// Sets `strict`.
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Sets `distance`.
+    pub fn with_distance(mut self, distance: bool) -> Self {
+        self.distance = distance;
+        self
+    }
+
+    /// Sets `line_numbers`.
+    pub fn with_line_numbers(mut self, line_numbers: bool) -> Self {
+        self.line_numbers = line_numbers;
+        self
+    }
+
+    /// Sets `echo_instructions`.
+    pub fn with_echo_instructions(mut self, echo_instructions: bool) -> Self {
+        self.echo_instructions = echo_instructions;
+        self
+    }
+
+    /// Sets `scent_uses`.
+    pub fn with_scent_uses(mut self, scent_uses: u32) -> Self {
+        self.scent_uses = Some(scent_uses);
+        self
+    }
+
+    /// Sets `default_grid`.
+    pub fn with_default_grid(mut self, default_grid: Coords) -> Self {
+        self.default_grid = Some(default_grid);
+        self
+    }
+
+    /// Formats an outcome for output, according to `format`,
+    /// `lost_marker`, `verbose`, `arrows` and `distance`.
+    pub fn report(&self, outcome: &RobotOutcome) -> Result<String> {
+        match self.format {
+            OutputFormat::Text => {
+                let mut line = format_outcome_with(outcome, &self.lost_marker, self.arrows);
+                if self.verbose {
+                    line.push_str(&format!(
+                        " (executed {} of {})",
+                        outcome.executed, outcome.instruction_count
+                    ));
+                }
+                if self.distance {
+                    line.push_str(&format!(" (dist {})", outcome.distance()));
+                }
+                if self.echo_instructions {
+                    line.push_str(&format!("  <= {}", outcome.raw_instructions));
+                }
+                if self.line_numbers {
+                    line = format!("L{}: {}", outcome.line, line);
+                }
+                Ok(line)
+            }
+            #[cfg(feature = "json")]
+            OutputFormat::Json => Ok(serde_json::to_string(outcome)?),
+        }
+    }
+
+    /// Something suspicious about an outcome, which is worth telling the
+    /// user about without changing the output. Only robots with no
+    /// instructions count, and only if `warn_empty` is set.
+    pub fn warning(&self, outcome: &RobotOutcome) -> Option<String> {
+        if self.warn_empty && outcome.instruction_count == 0 {
+            Some(format!(
+                "warning: robot starting at {} has no instructions",
+                outcome.start
+            ))
+        } else {
+            None
+        }
+    }
+
+    /// Whether an outcome should be reported at all, according to
+    /// `lost_only`.
+    pub fn wants(&self, outcome: &RobotOutcome) -> bool {
+        outcome.lost || !self.lost_only
+    }
+}
+
+/// Yields strings representing Robot end positions, ready to be
+/// passed to stdout, or Error if an input line is invalid. Errors are
+/// prefixed with the number of the line they refer to, counting from 1.
+/// Lines are trimmed, and blank lines and lines starting with `#` are
+/// skipped (apart from obstacles, which look like `# 2 2` and come before
+/// the first robot).
+#[cfg(feature = "std")]
+pub fn drive_robots(
+    lines: impl Iterator<Item = Result<String>>,
+) -> Result<impl Iterator<Item = Result<String>>> {
+    drive_robots_with(lines, &SimulationConfig::default())
+}
+
+/// Like drive_robots(), but with the rules tweaked by `options`.
+#[cfg(feature = "std")]
+pub fn drive_robots_with(
+    lines: impl Iterator<Item = Result<String>>,
+    options: &SimulationConfig,
+) -> Result<impl Iterator<Item = Result<String>>> {
+    let options = options.clone();
+    let outcomes = simulate_robots(lines, options.clone())?;
+    Ok(outcomes.filter_map(move |outcome| match outcome {
+        Ok(outcome) if !options.wants(&outcome) => None,
+        Ok(outcome) => Some(options.report(&outcome)),
+        Err(e) => Some(Err(e)),
+    }))
+}
+
+/// Like drive_robots_with(), but yields each RobotOutcome rather than
+/// formatting it. `options.format`, `options.lost_marker` and
+/// `options.lost_only` are ignored.
+#[cfg(feature = "std")]
+pub fn simulate_robots(
+    mut lines: impl Iterator<Item = Result<String>>,
+    options: SimulationConfig,
+) -> Result<impl Iterator<Item = Result<RobotOutcome>>> {
+    let mut simulation = Simulation::from_lines(&mut lines, options)?;
+
+    let lines = lines.map(Some).chain(std::iter::once(None));
+    Ok(lines.filter_map(move |line| match line {
+        Some(Ok(line)) => simulation.feed(Ok(&line)),
+        Some(Err(e)) => simulation.feed(Err(e)),
+        None => simulation.finish(),
+    }))
+}
+
+/// Like simulate_robots(), but yields `(start, end, lost)` for each robot.
+#[cfg(feature = "std")]
+pub fn drive_robots_paired(
+    lines: impl Iterator<Item = Result<String>>,
+    options: SimulationConfig,
+) -> Result<impl Iterator<Item = Result<(Robot, Robot, bool)>>> {
+    let outcomes = simulate_robots(lines, options)?;
+    Ok(outcomes.map(|outcome| outcome.map(|o| (o.start, o.robot, o.lost))))
+}
+
+/// Like simulate_robots(), but reads `input` one line at a time into a
+/// single buffer rather than allocating a String for every line. `emit` is
+/// called with each robot's outcome as soon as it is known, and can return
+/// Err to stop early. Returns the (last) grid, with its scents, once all of
+/// the robots have been driven.
+#[cfg(feature = "std")]
+pub fn simulate_robots_buffered(
+    mut input: impl BufRead,
+    options: SimulationConfig,
+    mut emit: impl FnMut(Result<RobotOutcome>) -> Result<()>,
+) -> Result<Grid> {
+    let mut buffer = String::new();
+    let mut number = 0;
+    let mut simulation = loop {
+        if read_line(&mut input, &mut buffer)? == 0 {
+            return Err(ParseError::EmptyInput.into());
+        }
+        number += 1;
+        if starts_simulation(&buffer, number, &options)? {
+            break Simulation::new(&buffer, number, options)?;
+        }
+    };
+
+    while read_line(&mut input, &mut buffer)? > 0 {
+        if let Some(outcome) = simulation.feed(Ok(&buffer)) {
+            emit(outcome)?;
+        }
+    }
+    if let Some(outcome) = simulation.finish() {
+        emit(outcome)?;
+    }
+    Ok(simulation.grid)
+}
+
+/// Joins the results from drive_robots() with "; ", to fit them all on one
+/// line. Stops at the first error.
+#[cfg(feature = "std")]
+pub fn join_on_one_line(results: impl Iterator<Item = Result<String>>) -> Result<String> {
+    Ok(results.collect::<Result<Vec<_>>>()?.join("; "))
+}
+
+/// What check_robots() found.
+#[cfg(feature = "std")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CheckSummary {
+    pub robots: usize,
+    pub max: Coords,
+}
+
+#[cfg(feature = "std")]
+impl Display for CheckSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "OK: {} robots, grid {}x{}",
+            self.robots, self.max.x, self.max.y
+        )
+    }
+}
+
+/// Running totals for the end of a batch run.
+#[cfg(feature = "std")]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Summary {
+    pub robots: usize,
+    pub lost: usize,
+}
+
+#[cfg(feature = "std")]
+impl Summary {
+    /// Counts one more robot, whether or not it gets reported.
+    pub fn add(&mut self, outcome: &RobotOutcome) {
+        self.robots += 1;
+        if outcome.lost {
+            self.lost += 1;
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} robots processed, {} lost", self.robots, self.lost)
+    }
+}
+
+/// Drives all of the robots, like drive_robots_with(), but only counts
+/// them. Returns the first error, if there is one.
+#[cfg(feature = "std")]
+pub fn count_robots(
+    lines: impl Iterator<Item = Result<String>>,
+    options: SimulationConfig,
+) -> Result<Summary> {
+    let mut summary = Summary::default();
+    for outcome in simulate_robots(lines, options)? {
+        summary.add(&outcome?);
+    }
+    Ok(summary)
+}
+
+/// Parses the same input as drive_robots(), without driving any robots.
+/// Returns the first error, if there is one.
+#[cfg(feature = "std")]
+pub fn check_robots(mut lines: impl Iterator<Item = Result<String>>) -> Result<CheckSummary> {
+    let mut simulation = Simulation::from_lines(&mut lines, SimulationConfig::default())?;
+
+    let mut robots = 0;
+    for line in lines {
+        let parsed = match line {
+            Ok(line) => simulation.parse(Ok(&line)),
+            Err(e) => simulation.parse(Err(e)),
+        };
+        if let Some(parsed) = parsed {
+            parsed?;
+            robots += 1;
+        }
+    }
+    if let Some(parsed) = simulation.parse_end() {
+        parsed?;
+        robots += 1;
+    }
+    Ok(CheckSummary {
+        robots,
+        max: simulation.grid.max,
+    })
+}
+
+/// A robot's starting position, and the instructions that it was given.
+pub type ParsedRobot = (Robot, Vec<Instruction>);
+
+/// Parses the same input as drive_robots(), without driving any robots,
+/// and returns all of them at once. If the input has more than one grid
+/// then this is the last one.
+#[cfg(feature = "std")]
+pub fn parse_input(
+    mut lines: impl Iterator<Item = Result<String>>,
+) -> Result<(Grid, Vec<ParsedRobot>)> {
+    let mut simulation = Simulation::from_lines(&mut lines, SimulationConfig::default())?;
+
+    let mut robots = vec![];
+    for line in lines {
+        let parsed = match line {
+            Ok(line) => simulation.parse(Ok(&line)),
+            Err(e) => simulation.parse(Err(e)),
+        };
+        if let Some(parsed) = parsed {
+            robots.push(parsed?);
+        }
+    }
+    if let Some(parsed) = simulation.parse_end() {
+        robots.push(parsed?);
+    }
+    Ok((simulation.grid, robots))
+}
+
+/// Returns 0 at the end of the input.
+#[cfg(feature = "std")]
+fn read_line(input: &mut impl BufRead, buffer: &mut String) -> Result<usize> {
+    buffer.clear();
+    Ok(input.read_line(buffer)?)
+}
+
+/// Blank lines and comments are not interesting.
+#[cfg(feature = "std")]
+fn is_interesting(line: &str) -> bool {
+    let line = line.trim();
+    !line.is_empty() && !line.starts_with('#')
+}
+
+/// Whether `line` is the first line that a Simulation needs, rather than
+/// something to skip. In strict mode, nothing can come before it.
+#[cfg(feature = "std")]
+fn starts_simulation(line: &str, number: usize, options: &SimulationConfig) -> Result<bool> {
+    if options.strict {
+        on_line(number, check_strict(line, false))?;
+    }
+    Ok(is_interesting(line))
+}
+
+/// In strict mode, a line must be its fields separated by single spaces,
+/// and a blank line must be empty and in a place where `blank_allowed`.
+#[cfg(feature = "std")]
+fn check_strict(line: &str, blank_allowed: bool) -> Result<()> {
+    let line = line.trim_end_matches(['\n', '\r']);
+    if line.is_empty() && blank_allowed {
+        return Ok(());
+    }
+    if line.trim().is_empty() {
+        bail!("strict mode: unexpected blank line");
+    }
+    if line.starts_with('#') {
+        bail!("strict mode: unexpected comment");
+    }
+    if line.split_whitespace().collect::<Vec<_>>().join(" ") != line {
+        bail!("strict mode: unexpected whitespace");
+    }
+    Ok(())
+}
+
+/// A line like `# 2 2`. Other lines starting with `#` are comments.
+#[cfg(feature = "std")]
+fn is_obstacle(line: &str) -> bool {
+    let fields = line.split_whitespace().collect::<Vec<_>>();
+    matches!(
+        fields.as_slice(),
+        ["#", x, y] if x.parse::<i32>().is_ok() && y.parse::<i32>().is_ok()
+    )
+}
+
+/// A line with two or four numbers, like `5 3`, or with two `key=value`
+/// fields, like `width=5 height=3`. Anything else in place of a position
+/// line is a position line, so that a typo gets the position line's
+/// error rather than starting a new grid.
+#[cfg(feature = "std")]
+fn is_grid_line(line: &str) -> bool {
+    let fields = line.split_whitespace().collect::<Vec<_>>();
+    let numbers = fields.iter().all(|field| field.parse::<i32>().is_ok());
+    let keywords = fields.iter().all(|field| field.contains('='));
+    matches!(fields.len(), 2 | 4) && (numbers || keywords)
+}
+
+/// The name from a line like `@rover1`.
+#[cfg(feature = "std")]
+fn parse_label(line: &str) -> Option<String> {
+    line.strip_prefix('@').map(|label| label.trim().to_owned())
+}
+
+/// Wraps any error in `result` in a LineError, to say where it came from.
+#[cfg(feature = "std")]
+fn on_line<T>(number: usize, result: Result<T>) -> Result<T> {
+    result.map_err(|error| {
+        LineError {
+            line: number,
+            error,
+        }
+        .into()
+    })
+}
+
+#[cfg(feature = "std")]
+fn parse_instruction_line(instruction_line: &str, macros: &Macros) -> Result<Vec<Instruction>> {
+    let instruction_line = instruction_line.trim();
+    let too_long = || {
+        Error::msg(format!(
+            "instruction sequence exceeds {} instructions",
+            MAX_INSTRUCTIONS
+        ))
+    };
+    if instruction_line
+        .chars()
+        .filter(|&c| !is_separator(c))
+        .count()
+        >= MAX_INSTRUCTIONS
+    {
+        return Err(too_long());
+    }
+    // Counts and distances make short lines long, so check again.
+    let instructions = parse_instructions_with_macros(instruction_line, macros)?;
+    if count_steps(&instructions) >= MAX_INSTRUCTIONS {
+        return Err(too_long());
+    }
+    Ok(instructions)
+}
+
+/// Expects a line like `define SQUARE FRFRFRFR`, and returns the name and
+/// the instructions.
+#[cfg(feature = "std")]
+fn parse_macro(define_line: &str) -> Result<(String, Vec<Instruction>)> {
+    let fields = define_line.split_whitespace().collect::<Vec<_>>();
+    let (name, sequence) = match fields.as_slice() {
+        ["define", name, sequence] => (*name, *sequence),
+        _ => bail!("macro must be written as define NAME SEQUENCE"),
+    };
+    let starts_with_instruction = name.starts_with(|c| INSTRUCTION_LETTERS.contains(c));
+    if name.len() < 2 || starts_with_instruction || !name.chars().all(|c| c.is_ascii_uppercase()) {
+        bail!("macro name must be capital letters, not starting with F, B, L, R, U or T");
+    }
+    Ok((name.to_owned(), parse_instructions(sequence)?))
+}
+
+/// The state that drive_robots() and friends carry from one line of input
+/// to the next.
+#[cfg(feature = "std")]
+struct Simulation {
+    grid: Grid,
+    options: SimulationConfig,
+    /// The number of the last line that was fed in.
+    line_number: usize,
+    /// The robot from the last position line, waiting for its instructions.
+    start: Option<Result<Robot>>,
+    /// The number of the position line for `start`.
+    start_line: usize,
+    /// The number of the position line for the robot that parse() last
+    /// returned, for RobotOutcome::line.
+    robot_line: usize,
+    /// The instruction line (or lines) for that robot, for
+    /// RobotOutcome::raw_instructions.
+    robot_instructions: String,
+    /// Obstacles can only be given before the first robot.
+    seen_robot: bool,
+    /// With `SimulationConfig::multiline_instructions`, the instruction lines seen
+    /// so far for `start`, and the number of the first one.
+    instructions: Option<(usize, String)>,
+    /// A grid line that ended a robot's instructions. It replaces `grid`
+    /// once that robot has been driven.
+    next_grid: Option<Grid>,
+    /// How many robots have been driven (or failed to parse) so far.
+    robots: usize,
+    /// The label for the next robot, from an `@name` line.
+    label: Option<String>,
+    /// From `define` lines before the first robot. They last until the end
+    /// of the input, even if there is a new grid.
+    macros: Macros,
+    /// Grids that have been replaced by a later grid line, in order. Only
+    /// kept if this is Some.
+    retired: Option<Vec<Grid>>,
+    /// Whether the last line was blank, for `SimulationConfig::strict`.
+    after_blank: bool,
+}
+
+#[cfg(feature = "std")]
+impl Simulation {
+    /// Starts with the grid from `grid_line`, or with
+    /// `SimulationConfig::default_grid` if it is set and `grid_line` isn't a grid
+    /// line after all.
+    fn new(grid_line: &str, line_number: usize, options: SimulationConfig) -> Result<Self> {
+        if let Some(max) = &options.default_grid {
+            if !is_grid_line(grid_line) {
+                let grid = Grid::new(max.x, max.y);
+                let mut simulation = Simulation {
+                    line_number: line_number - 1,
+                    ..Simulation::with_grid(grid, options)
+                };
+                // The first interesting line can't finish a robot, so this
+                // can only be an error.
+                if let Some(Err(e)) = simulation.feed(Ok(grid_line)) {
+                    return Err(e);
+                }
+                return Ok(simulation);
+            }
+        }
+        let grid = Simulation::parse_grid(grid_line, line_number, &options)?;
+        Ok(Simulation {
+            line_number,
+            ..Simulation::with_grid(grid, options)
+        })
+    }
+
+    /// Like new(), but with a grid that has already been parsed.
+    fn with_grid(grid: Grid, options: SimulationConfig) -> Self {
+        Simulation {
+            grid: Simulation::configure(grid, &options),
+            options,
+            line_number: 0,
+            start: None,
+            start_line: 0,
+            robot_line: 0,
+            robot_instructions: String::new(),
+            seen_robot: false,
+            instructions: None,
+            next_grid: None,
+            robots: 0,
+            label: None,
+            macros: Macros::new(),
+            retired: None,
+            after_blank: false,
+        }
+    }
+
+    fn parse_grid(grid_line: &str, line_number: usize, options: &SimulationConfig) -> Result<Grid> {
+        let grid = on_line(line_number, grid_line.trim().parse())?;
+        Ok(Simulation::configure(grid, options))
+    }
+
+    /// Applies the parts of `options` that the grid needs to know about.
+    fn configure(mut grid: Grid, options: &SimulationConfig) -> Grid {
+        grid.wrap = options.wrap;
+        grid.y_down = options.y_down;
+        grid.stride = options.stride;
+        grid.ghost = options.ghost;
+        grid.eight_way = options.eight_way;
+        match options.scent_uses {
+            Some(uses) => grid.with_scent_policy(Fading::new(uses)),
+            None => grid,
+        }
+    }
+
+    /// Skips to the grid line, and sets up a Simulation for the lines
+    /// after it.
+    fn from_lines(
+        lines: &mut impl Iterator<Item = Result<String>>,
+        options: SimulationConfig,
+    ) -> Result<Self> {
+        for (line, number) in lines.zip(1..) {
+            let line = on_line(number, line)?;
+            if starts_simulation(&line, number, &options)? {
+                return Simulation::new(&line, number, options);
+            }
+        }
+        Err(ParseError::EmptyInput.into())
+    }
+
+    /// Returns the robot's outcome once its instruction line has been fed
+    /// in. Before the first robot, lines like `# 2 2` put obstacles on the
+    /// grid, and lines like `define SQUARE FRFRFRFR` define macros. Other
+    /// lines starting with `#` are comments. A grid line in place of a
+    /// position line starts again on a fresh grid, and a line like
+    /// `@rover1` gives the next robot a label.
+    fn feed(&mut self, line: Result<&str>) -> Option<Result<RobotOutcome>> {
+        let parsed = self.parse(line)?;
+        Some(self.drive_parsed(parsed))
+    }
+
+    /// Drives the last robot, if its instructions were still being
+    /// collected when the input ran out.
+    fn finish(&mut self) -> Option<Result<RobotOutcome>> {
+        let parsed = self.parse_end();
+        let outcome = parsed.map(|parsed| self.drive_parsed(parsed));
+        self.advance_grid();
+        outcome
+    }
+
+    /// Like finish(), but stops short of driving the robot. A position line
+    /// with no instruction line after it is an error.
+    fn parse_end(&mut self) -> Option<Result<ParsedRobot>> {
+        if self.options.multiline_instructions {
+            return self.take_multiline_robot();
+        }
+        let start = self.start.take()?;
+        Some(start.and_then(|_| {
+            on_line(
+                self.line_number,
+                Err(Error::msg(
+                    "robot position without instructions at end of input",
+                )),
+            )
+        }))
+    }
+
+    /// Drives the robot, if it parsed. A lost robot is an error if
+    /// `SimulationConfig::stop_on_lost` is set.
+    fn drive_parsed(&mut self, parsed: Result<ParsedRobot>) -> Result<RobotOutcome> {
+        self.robots += 1;
+        if matches!(self.options.max_robots, Some(max) if self.robots > max) {
+            return on_line(self.line_number, Err(Error::msg("robot limit exceeded")));
+        }
+        let (start, instructions) = parsed?;
+        if let Some(max) = self.options.max_steps {
+            if count_steps(&instructions) > max {
+                let too_long = format!("robot starting at {} takes more than {} steps", start, max);
+                return on_line(self.line_number, Err(Error::msg(too_long)));
+            }
+        }
+        let outcome = self.drive(start, &instructions);
+        if outcome.lost && self.options.stop_on_lost {
+            let lost = format!("robot lost at {}", outcome.robot);
+            return on_line(self.line_number, Err(Error::msg(lost)));
+        }
+        Ok(outcome)
+    }
+
+    /// Like feed(), but stops short of driving the robot.
+    fn parse(&mut self, line: Result<&str>) -> Option<Result<(Robot, Vec<Instruction>)>> {
+        self.advance_grid();
+        self.line_number += 1;
+        let number = self.line_number;
+        if let (true, Ok(l)) = (self.options.strict, &line) {
+            // Only one blank line, and only between robots.
+            let blank_allowed = self.seen_robot && self.start.is_none() && !self.after_blank;
+            self.after_blank = l.trim().is_empty();
+            if let Err(e) = check_strict(l, blank_allowed) {
+                return Some(on_line(number, Err(e)));
+            }
+        }
+        let line = line.map(str::trim);
+        if self.options.multiline_instructions && self.start.is_some() {
+            return self.parse_multiline(number, line);
+        }
+        if let Ok(l) = line {
+            if !self.seen_robot && is_obstacle(l) {
+                let added = on_line(number, self.grid.add_obstacle(l));
+                return added.err().map(Err);
+            }
+            if !self.seen_robot && l.starts_with("define ") {
+                let defined = on_line(number, parse_macro(l)).map(|(name, instructions)| {
+                    self.macros.insert(name, instructions);
+                });
+                return defined.err().map(Err);
+            }
+            if !is_interesting(l) {
+                return None;
+            }
+            if self.start.is_none() {
+                if let Some(label) = parse_label(l) {
+                    self.label = Some(label);
+                    return None;
+                }
+            }
+            if self.start.is_none() && is_grid_line(l) {
+                let grid = Simulation::parse_grid(l, number, &self.options);
+                return match grid {
+                    Ok(grid) => {
+                        self.replace_grid(grid);
+                        None
+                    }
+                    Err(e) => Some(Err(e)),
+                };
+            }
+        }
+        match self.start.take() {
+            None => {
+                self.start_robot(number, line);
+                None
+            }
+            Some(start) => Some(start.and_then(|start| {
+                self.robot_line = self.start_line;
+                let instructions = on_line(
+                    number,
+                    line.and_then(|l| {
+                        self.robot_instructions = l.to_owned();
+                        parse_instruction_line(l, &self.macros)
+                    }),
+                )?;
+                Ok((start, instructions))
+            })),
+        }
+    }
+
+    /// Like the end of parse(), but for when instructions can be spread
+    /// over several lines. They are collected until the next position or
+    /// grid line, and then the robot is ready to drive.
+    fn parse_multiline(
+        &mut self,
+        number: usize,
+        line: Result<&str>,
+    ) -> Option<Result<(Robot, Vec<Instruction>)>> {
+        if let Ok(l) = line {
+            if !is_interesting(l) {
+                return None;
+            }
+            if l.split_whitespace().count() == 1 && !l.starts_with('@') {
+                let (_, instructions) = self
+                    .instructions
+                    .get_or_insert_with(|| (number, String::new()));
+                instructions.push_str(l);
+                return None;
+            }
+        }
+        let finished = self.take_multiline_robot();
+        match line {
+            Ok(l) if l.starts_with('@') => self.label = parse_label(l),
+            Ok(l) if is_grid_line(l) => match Simulation::parse_grid(l, number, &self.options) {
+                Ok(grid) => self.next_grid = Some(grid),
+                // Reported in place of the next robot.
+                Err(e) => self.start = Some(Err(e)),
+            },
+            line => self.start_robot(number, line),
+        }
+        finished
+    }
+
+    fn start_robot(&mut self, number: usize, line: Result<&str>) {
+        self.seen_robot = true;
+        self.start_line = number;
+        let grid = &self.grid;
+        let label = self.label.take();
+        let start = line.and_then(str::parse).and_then(|robot: Robot| {
+            if robot.is_out_of_bounds(grid) {
+                bail!("robot starts outside grid");
+            }
+            Ok(Robot { label, ..robot })
+        });
+        self.start = Some(on_line(number, start));
+    }
+
+    fn take_multiline_robot(&mut self) -> Option<Result<(Robot, Vec<Instruction>)>> {
+        if !self.options.multiline_instructions {
+            return None;
+        }
+        let start = self.start.take()?;
+        self.robot_line = self.start_line;
+        let (number, instructions) = self.instructions.take().unwrap_or_default();
+        let parsed = start.and_then(|start| {
+            let instructions =
+                on_line(number, parse_instruction_line(&instructions, &self.macros))?;
+            Ok((start, instructions))
+        });
+        self.robot_instructions = instructions;
+        Some(parsed)
+    }
+
+    fn advance_grid(&mut self) {
+        if let Some(grid) = self.next_grid.take() {
+            self.replace_grid(grid);
+        }
+    }
+
+    fn replace_grid(&mut self, grid: Grid) {
+        let old = std::mem::replace(&mut self.grid, grid);
+        if let Some(retired) = &mut self.retired {
+            retired.push(old);
+        }
+        self.seen_robot = false;
+    }
+
+    fn drive(&mut self, start: Robot, instructions: &[Instruction]) -> RobotOutcome {
+        let grid = &mut self.grid;
+        let first = start.clone();
+        let trace = if self.options.trace {
+            trace_robot(grid, start.clone(), instructions)
+        } else {
+            vec![]
+        };
+        let mut blocked = false;
+        let mut last = None;
+        let mut executed = 0;
+        let mut extent = Extent::of(&start.coords);
+        let followed = until_halt(instructions);
+        let result = if let Some(eighths) = net_turn(grid, followed) {
+            // Like try_all_instructions(), a robot that only turns can't
+            // be blocked, lost or saved, so skip straight to the end.
+            executed = followed.len();
+            Ok(Robot {
+                bearing: start.bearing.rotated_by_eighths(eighths),
+                ..start
+            })
+        } else {
+            followed.iter().try_fold(start, |current, instruction| {
+                blocked |= current.is_blocked(grid, instruction);
+                last = Some(instruction);
+                if let Instruction::Scent = instruction {
+                    grid.apply_scent(&current);
+                }
+                let next = grid.move_robot(current, instruction)?;
+                // Moves are in straight lines, so the ends are enough.
+                extent.add(&next.coords);
+                executed += 1;
+                Ok(next)
+            })
+        };
+        let (robot, lost) = match result {
+            Ok(alive) => {
+                if self.options.collisions {
+                    grid.occupy(&alive);
+                }
+                (alive, false)
+            }
+            Err(dead) => {
+                grid.apply_scent(&dead);
+                // A long move falls off on its first step past the edge.
+                let step = match last {
+                    Some(Instruction::Forward(_)) => Some(&Instruction::F),
+                    step => step,
+                };
+                let attempted = step.and_then(|step| dead.destination(grid, step));
+                extent.add(&dead.coords);
+                if let Some(attempted) = &attempted {
+                    extent.add(&attempted.coords);
+                }
+                match (self.options.lost_position, attempted) {
+                    (LostPosition::Attempted, Some(attempted)) => (attempted, true),
+                    _ => (dead, true),
+                }
+            }
+        };
+        RobotOutcome {
+            robot,
+            lost,
+            blocked,
+            trace,
+            start: first,
+            instruction_count: instructions.len(),
+            executed,
+            extent,
+            line: self.robot_line,
+            raw_instructions: self.robot_instructions.clone(),
+        }
+    }
+}
+
+/// How many times a robot would move or turn, if it followed all of
+/// `instructions`.
+#[cfg(feature = "std")]
+fn count_steps(instructions: &[Instruction]) -> usize {
+    instructions
+        .iter()
+        .map(|instruction| match instruction {
+            Instruction::Forward(steps) => *steps as usize,
+            _ => 1,
+        })
+        .sum()
+}
+
+/// Joins the lines of several inputs into one, e.g. a grid from one file
+/// and robots from others. Empty inputs are skipped, since they have no
+/// lines. Line numbers in errors count across all of the inputs.
+#[cfg(feature = "std")]
+pub fn chain_lines<R: BufRead>(
+    sources: impl IntoIterator<Item = R>,
+) -> impl Iterator<Item = Result<String>> {
+    sources
+        .into_iter()
+        .flat_map(|source| source.lines().map(|line| Ok(line?)))
+}
+
+/// Filter for removing empty lines. drive_robots() skips them itself, so
+/// this is only needed by callers that want to see fewer lines.
+#[cfg(feature = "std")]
+pub fn no_empty_lines(l: &Result<String>) -> bool {
+    match l {
+        Ok(l) => !l.is_empty(),
+        Err(_) => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(input: &str) -> impl Iterator<Item = Result<String>> + '_ {
+        input
+            .lines()
+            .map(|l| Ok(l.trim().to_owned()))
+            .filter(no_empty_lines)
+    }
+
+    fn join(input: impl Iterator<Item = Result<String>>) -> Result<String> {
+        Ok(input
+            .collect::<Result<Vec<String>>>()?
+            .join("\n")
+            .to_owned())
+    }
+
+    fn format(input: &str) -> Result<String> {
+        join(split(input).filter(no_empty_lines))
+    }
+
+    #[test]
+    fn example_input_produces_example_output() -> Result<()> {
+        let input = r#"
+        5 3
+        1 1 E
+        RFRFRFRF
+        3 2 N
+        FRRFLLFFRRFLL
+        0 3 W
+        LLFFFLFLFL
+        "#;
+        let output = join(drive_robots(split(input)).flatten_to_iterator())?;
+
+        let expected_output = format(
+            r#"
+            1 1 E
+            3 3 N LOST
+            2 3 S
+            "#,
+        )?;
+        assert_eq!(output, expected_output);
+        Ok(())
+    }
+
+    #[test]
+    fn empty_input_produces_error() -> Result<()> {
+        let input = r#""#;
+        let output = drive_robots(split(input))
+            .flatten_to_iterator()
+            .next()
+            .ok_or_else(|| Error::msg("should output something"))?;
+
+        assert_eq!(output.unwrap_err().to_string(), "input must not be empty");
+        Ok(())
+    }
+
+    #[test]
+    fn reversing_off_the_edge_is_lost_and_leaves_a_scent() -> Result<()> {
+        let input = r#"
+        5 3
+        0 0 N
+        B
+        0 0 N
+        BF
+        "#;
+        let output = join(drive_robots(split(input)).flatten_to_iterator())?;
+
+        let expected_output = format(
+            r#"
+            0 0 N LOST
+            0 1 N
+            "#,
+        )?;
+        assert_eq!(output, expected_output);
+        Ok(())
+    }
+
+    #[test]
+    fn diagonal_forwards_moves_both_axes() -> Result<()> {
+        let grid: Grid = "5 3".to_owned().try_into()?;
+        let start: Robot = "1 1 NE".to_owned().try_into()?;
+
+        let end = start
+            .try_all_instructions(&grid, &[Instruction::F])
+            .unwrap();
+
+        assert_eq!(end.coords, Coords { x: 2, y: 2 });
+        assert_eq!(end.bearing.to_string(), "NE");
+        Ok(())
+    }
+
+    #[test]
+    fn right_turns_come_back_around() -> Result<()> {
+        let mut bearing = Bearing::N;
+        let mut seen = vec![];
+        for _ in 0..8 {
+            bearing = bearing.rotate(&Rotation::R);
+            seen.push(bearing.to_string());
+        }
+        assert_eq!(seen, vec!["NE", "E", "SE", "S", "SW", "W", "NW", "N"]);
+
+        let mut bearing = Bearing::NE;
+        let mut seen = vec![];
+        for _ in 0..4 {
+            bearing = bearing.quarter_turn(&Rotation::R);
+            seen.push(bearing.to_string());
+        }
+        assert_eq!(seen, vec!["SE", "SW", "NW", "NE"]);
+
+        let input = "5 3\n1 1 N\nRF\n1 1 N\nRFLF\n1 1 N\nRRRL";
+        let options = SimulationConfig::default().with_eight_way(true);
+        let output = drive_robots_with(split(input), &options)?;
+        assert_eq!(join(output)?, "2 2 NE\n2 3 N\n1 1 E");
+        let output = drive_robots(split(input))?;
+        assert_eq!(join(output)?, "2 1 E\n2 2 N\n1 1 S");
+        Ok(())
+    }
+
+    #[test]
+    fn oversized_grid_produces_error() -> Result<()> {
+        let input = r#"
+        51 3
+        1 1 E
+        RFRFRFRF
+        "#;
+        let output = drive_robots(split(input))
+            .flatten_to_iterator()
+            .next()
+            .ok_or_else(|| Error::msg("should output something"))?;
+
+        assert_eq!(
+            output.unwrap_err().to_string(),
+            "line 1: grid coordinate exceeds maximum of 50"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn negative_grid_produces_error() {
+        let grid: Result<Grid> = "5 -1".to_owned().try_into();
+        assert_eq!(
+            grid.unwrap_err().to_string(),
+            "grid coordinate must not be negative"
+        );
+    }
+
+    #[test]
+    fn grid_within_maximum_is_accepted() -> Result<()> {
+        let _: Grid = "5 3".to_owned().try_into()?;
+        let _: Grid = "50 50".to_owned().try_into()?;
+        Ok(())
+    }
+
+    #[test]
+    fn overlong_instruction_line_produces_error() -> Result<()> {
+        let input = format!(
+            "5 3\n1 1 E\n{}\n1 1 E\n{}\n",
+            "L".repeat(100),
+            "L".repeat(99)
+        );
+        let mut output = drive_robots(split(&input)).flatten_to_iterator();
+
+        assert_eq!(
+            output.next().unwrap().unwrap_err().to_string(),
+            "line 3: instruction sequence exceeds 100 instructions"
+        );
+        assert_eq!(output.next().unwrap()?, "1 1 S");
+        Ok(())
+    }
+
+    #[test]
+    fn repeat_counts_expand_to_individual_instructions() -> Result<()> {
+        assert_eq!(format!("{:?}", parse_instructions("*3F")?), "[F, F, F]");
+        assert_eq!(
+            format!("{:?}", parse_instructions("*2R*3F")?),
+            "[Turn(R), Turn(R), F, F, F]"
+        );
+        assert_eq!(
+            format!("{:?}", parse_instructions("FRLF")?),
+            "[F, Turn(R), Turn(L), F]"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn trailing_repeat_count_produces_error() {
+        assert_eq!(
+            parse_instructions("F*5").unwrap_err().to_string(),
+            "repeat count must be followed by an instruction"
+        );
+        assert_eq!(
+            parse_instructions("5").unwrap_err().to_string(),
+            "repeat count must be written like *3F"
+        );
+    }
+
+    #[test]
+    fn wrapping_grid_brings_robots_back_on_the_other_side() -> Result<()> {
+        let input = r#"
+        5 3
+        5 1 E
+        F
+        0 0 SW
+        F
+        "#;
+        let options = SimulationConfig {
+            wrap: true,
+            ..Default::default()
+        };
+        let output = join(drive_robots_with(split(input), &options).flatten_to_iterator())?;
+
+        let expected_output = format(
+            r#"
+            0 1 E
+            5 3 SW
+            "#,
+        )?;
+        assert_eq!(output, expected_output);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn example_input_produces_json_output() -> Result<()> {
+        let input = r#"
+        5 3
+        1 1 E
+        RFRFRFRF
+        3 2 N
+        FRRFLLFFRRFLL
+        0 3 W
+        LLFFFLFLFL
+        "#;
+        let options = SimulationConfig {
+            format: OutputFormat::Json,
+            ..Default::default()
+        };
+        let output = join(drive_robots_with(split(input), &options).flatten_to_iterator())?;
+
+        let expected_output = format(
+            r#"
+            {"x":1,"y":1,"bearing":"E","lost":false}
+            {"x":3,"y":3,"bearing":"N","lost":true}
+            {"x":2,"y":3,"bearing":"S","lost":false}
+            "#,
+        )?;
+        assert_eq!(output, expected_output);
+        Ok(())
+    }
+
+    #[test]
+    fn bad_robot_does_not_stop_other_robots() -> Result<()> {
+        let input = r#"
+        5 3
+        1 1 E
+        RFRFRFRF
+        3 2 Q
+        FRRFLLFFRRFLL
+        0 3 W
+        LLFFFLFLFL
+        "#;
+        let output = drive_robots(split(input))
+            .flatten_to_iterator()
+            .map(|result| result.unwrap_or_else(|e| e.to_string()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            output,
+            vec![
+                "1 1 E",
+                "line 4: Bearing must be one of N, NE, E, SE, S, SW, W, or NW",
+                // No scent, because the robot on line 4 never ran.
+                "3 3 N LOST",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn position_typos_do_not_start_a_new_grid() -> Result<()> {
+        let input = "5 3\n1 1 E\nR\n0 0 N X\nF\n3 2 Q X\nF\n5 3 N\nF\n";
+        let output = drive_robots(split(input))
+            .flatten_to_iterator()
+            .map(|result| result.unwrap_or_else(|e| e.to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            output,
+            vec![
+                "1 1 S",
+                "line 4: position line has too many fields",
+                "line 6: Bearing must be one of N, NE, E, SE, S, SW, W, or NW",
+                "5 3 N LOST",
+            ]
+        );
+        assert!(is_grid_line("-2 -2 2 2"));
+        assert!(is_grid_line("height=3 width=5"));
+        Ok(())
+    }
+
+    #[test]
+    fn errors_report_the_line_they_came_from() -> Result<()> {
+        let errors = |input| {
+            drive_robots(split(input))
+                .flatten_to_iterator()
+                .filter_map(|result| result.err())
+                .map(|e| e.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            errors("5 3\n1 1 E\nR\n3 2 Q\nF\n0 3 W\nLX\n"),
+            vec![
+                "line 4: Bearing must be one of N, NE, E, SE, S, SW, W, or NW",
+                "line 7: instruction must be F, B, L, R, U, S, H, or Tx,y",
+            ]
+        );
+        assert_eq!(
+            errors("5 3 1\n1 1 E\nR\n"),
+            vec!["line 1: grid line has too many fields"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn teleport_jumps_to_absolute_coordinates() -> Result<()> {
+        let input = r#"
+        5 3
+        1 1 E
+        T2,3F
+        1 1 E
+        T9,9F
+        1 1 N
+        T-1,0F
+        "#;
+        let output = join(drive_robots(split(input)).flatten_to_iterator())?;
+
+        // The second robot leaves a scent at 1 1, so the third one ignores
+        // its teleport.
+        let expected_output = format(
+            r#"
+            3 3 E
+            1 1 E LOST
+            1 2 N
+            "#,
+        )?;
+        assert_eq!(output, expected_output);
+        Ok(())
+    }
+
+    #[test]
+    fn malformed_teleport_produces_error() {
+        assert_eq!(
+            parse_instructions("T2").unwrap_err().to_string(),
+            "teleport must be written as Tx,y"
+        );
+        assert_eq!(
+            parse_instructions("T,3").unwrap_err().to_string(),
+            "teleport must be written as Tx,y"
+        );
+    }
+
+    #[test]
+    fn colliding_robots_stop_before_parked_robots() -> Result<()> {
+        let input = r#"
+        5 3
+        1 1 E
+        F
+        0 1 E
+        FFF
+        "#;
+        let options = SimulationConfig {
+            collisions: true,
+            ..Default::default()
+        };
+        let output = join(drive_robots_with(split(input), &options).flatten_to_iterator())?;
+
+        let expected_output = format(
+            r#"
+            2 1 E
+            1 1 E BLOCKED
+            "#,
+        )?;
+        assert_eq!(output, expected_output);
+        Ok(())
+    }
+
+    #[test]
+    fn buffered_reader_matches_iterator() -> Result<()> {
+        let input = r#"
+        5 3
+        1 1 E
+        RFRFRFRF
+
+        3 2 Q
+        FRRFLLFFRRFLL
+
+        0 3 W
+        LLFFFLFLFL
+        "#;
+        let mut buffered = vec![];
+        simulate_robots_buffered(input.as_bytes(), SimulationConfig::default(), |outcome| {
+            let line = outcome.map(|o| format_outcome(&o));
+            buffered.push(line.unwrap_or_else(|e| e.to_string()));
+            Ok(())
+        })?;
+
+        let iterated = drive_robots(input.lines().map(|l| Ok(l.to_owned())))
+            .flatten_to_iterator()
+            .map(|result| result.unwrap_or_else(|e| e.to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(buffered, iterated);
+        assert_eq!(buffered[0], "1 1 E");
+        assert_eq!(
+            buffered[1],
+            "line 6: Bearing must be one of N, NE, E, SE, S, SW, W, or NW"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn grid_contains_its_corners_and_nothing_outside() -> Result<()> {
+        let grid: Grid = "5 3".to_owned().try_into()?;
+        assert!(grid.contains(0, 0));
+        assert!(grid.contains(5, 3));
+        assert!(grid.contains(0, 3));
+        assert!(grid.contains(5, 0));
+        assert!(!grid.contains(-1, 0));
+        assert!(!grid.contains(0, -1));
+        assert!(!grid.contains(6, 3));
+        assert!(!grid.contains(5, 4));
+        Ok(())
+    }
+
+    #[test]
+    fn grids_robots_and_bearings_can_be_parsed_from_str() -> Result<()> {
+        let grid = "5 3".parse::<Grid>()?;
+        assert_eq!(grid.max, Coords { x: 5, y: 3 });
+        assert_eq!(
+            "5".parse::<Grid>().unwrap_err().to_string(),
+            "missing y coordinate"
+        );
+
+        let robot = "1 1 E".parse::<Robot>()?;
+        assert_eq!(robot.coords, Coords { x: 1, y: 1 });
+        assert_eq!(robot.bearing.to_string(), "E");
+        assert_eq!(
+            "1 1".parse::<Robot>().unwrap_err().to_string(),
+            "missing bearing"
+        );
+
+        assert_eq!("SW".parse::<Bearing>()?.to_string(), "SW");
+        assert_eq!(
+            "Q".parse::<Bearing>().unwrap_err().to_string(),
+            "Bearing must be one of N, NE, E, SE, S, SW, W, or NW"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn obstacles_block_robots_without_losing_them() -> Result<()> {
+        let input = r#"
+        5 3
+        # 2 1
+        # 0 3
+        1 1 E
+        FLF
+        0 2 N
+        F
+        "#;
+        let output = join(drive_robots(split(input)).flatten_to_iterator())?;
+
+        let expected_output = format(
+            r#"
+            1 2 N
+            0 2 N
+            "#,
+        )?;
+        assert_eq!(output, expected_output);
+        Ok(())
+    }
+
+    #[test]
+    fn obstacles_outside_the_grid_produce_error() -> Result<()> {
+        let input = r#"
+        5 3
+        # 6 1
+        1 1 E
+        F
+        "#;
+        let output = drive_robots(split(input))
+            .flatten_to_iterator()
+            .next()
+            .ok_or_else(|| Error::msg("should output something"))?;
+
+        assert_eq!(
+            output.unwrap_err().to_string(),
+            "line 2: obstacle is outside the grid"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn check_counts_robots_without_driving_them() -> Result<()> {
+        let input = r#"
+        5 3
+        1 1 E
+        RFRFRFRF
+        3 2 N
+        FRRFLLFFRRFLL
+        0 3 W
+        LLFFFLFLFL
+        "#;
+        let summary = check_robots(split(input))?;
+        assert_eq!(summary.to_string(), "OK: 3 robots, grid 5x3");
+        Ok(())
+    }
+
+    #[test]
+    fn check_reports_bad_instructions() {
+        let input = r#"
+        5 3
+        1 1 E
+        RFRFRFRF
+        3 2 N
+        FRRFLXFFRRFLL
+        "#;
+        assert_eq!(
+            check_robots(split(input)).unwrap_err().to_string(),
+            "line 5: instruction must be F, B, L, R, U, S, H, or Tx,y"
+        );
+    }
+
+    #[test]
+    fn comments_and_blank_lines_are_skipped() -> Result<()> {
+        let input = r#"
+        # The example from the challenge, with some notes.
+        5 3
+
+        # This one stays on the grid.
+        1 1 E
+        # Comments between the position and instructions are fine too.
+        RFRFRFRF
+
+          3 2 N   
+        FRRFLLFFRRFLL
+        # This one leaves a scent at 3 3, which saves the next one.
+        0 3 W
+
+        LLFFFLFLFL
+        "#;
+        let output =
+            join(drive_robots(input.lines().map(|l| Ok(l.to_owned()))).flatten_to_iterator())?;
+
+        let expected_output = format(
+            r#"
+            1 1 E
+            3 3 N LOST
+            2 3 S
+            "#,
+        )?;
+        assert_eq!(output, expected_output);
+        Ok(())
+    }
+
+    #[test]
+    fn simulate_robots_yields_structured_outcomes() -> Result<()> {
+        let input = r#"
+        5 3
+        1 1 E
+        RFRFRFRF
+        3 2 N
+        FRRFLLFFRRFLL
+        0 3 W
+        LLFFFLFLFL
+        "#;
+        let outcomes = simulate_robots(split(input), SimulationConfig::default())
+            .flatten_to_iterator()
+            .collect::<Result<Vec<_>>>()?;
+
+        assert_eq!(outcomes.len(), 3);
+        let lost = &outcomes[1];
+        assert_eq!(lost.robot.coords, Coords { x: 3, y: 3 });
+        assert_eq!(lost.robot.bearing.to_string(), "N");
+        assert!(lost.lost);
+        assert!(!outcomes[0].lost);
+        assert!(!outcomes[2].lost);
+        assert_eq!(format_outcome(lost), "3 3 N LOST");
+        Ok(())
+    }
+
+    #[test]
+    fn u_turn_reverses_bearing_without_moving() -> Result<()> {
+        let grid: Grid = "5 3".parse()?;
+        let start: Robot = "1 2 N".parse()?;
+        let end = start
+            .try_all_instructions(&grid, &parse_instructions("U")?)
+            .unwrap();
+
+        assert_eq!(end.coords, Coords { x: 1, y: 2 });
+        assert_eq!(end.bearing.to_string(), "S");
+        assert_eq!(Bearing::NE.reverse().to_string(), "SW");
+        Ok(())
+    }
+
+    #[test]
+    fn lost_marker_can_be_changed() -> Result<()> {
+        let input = r#"
+        5 3
+        3 2 N
+        FRRFLLFFRRFLL
+        "#;
+        let options = SimulationConfig {
+            lost_marker: "DEAD".to_owned(),
+            ..Default::default()
+        };
+        let output = join(drive_robots_with(split(input), &options).flatten_to_iterator())?;
+
+        assert_eq!(output, "3 3 N DEAD");
+        Ok(())
+    }
+
+    #[test]
+    fn robots_and_grids_display_like_their_input() -> Result<()> {
+        let robot: Robot = "1 1 E".parse()?;
+        assert_eq!(robot.to_string(), "1 1 E");
+        let robot: Robot = "0 3 NW".parse()?;
+        assert_eq!(robot.to_string(), "0 3 NW");
+
+        let grid: Grid = "5 3".parse()?;
+        assert_eq!(grid.to_string(), "5 3");
+        Ok(())
+    }
+
+    #[test]
+    fn trace_records_every_step() -> Result<()> {
+        let grid: Grid = "5 3".parse()?;
+        let start: Robot = "1 1 E".parse()?;
+        let trace = trace_robot(&grid, start, &parse_instructions("RFRFRFRF")?);
+
+        let trace = trace.iter().map(Robot::to_string).collect::<Vec<_>>();
+        assert_eq!(
+            trace,
+            vec!["1 1 S", "1 0 S", "1 0 W", "0 0 W", "0 0 N", "0 1 N", "0 1 E", "1 1 E"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn trace_stays_put_once_lost() -> Result<()> {
+        let grid: Grid = "5 3".parse()?;
+        let start: Robot = "3 2 N".parse()?;
+        let trace = trace_robot(&grid, start, &parse_instructions("FFRF")?);
+
+        let trace = trace.iter().map(Robot::to_string).collect::<Vec<_>>();
+        assert_eq!(trace, vec!["3 3 N", "3 3 N", "3 3 N", "3 3 N"]);
+        Ok(())
+    }
+
+    #[test]
+    fn lowercase_is_accepted() -> Result<()> {
+        assert_eq!(Bearing::try_from("e")?.to_string(), "E");
+        assert_eq!(Bearing::try_from("nw")?.to_string(), "NW");
+        assert!(matches!(Instruction::try_from('f')?, Instruction::F));
+        assert_eq!(
+            Bearing::try_from("x").err().unwrap().to_string(),
+            "Bearing must be one of N, NE, E, SE, S, SW, W, or NW"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn grid_line_starts_a_fresh_grid() -> Result<()> {
+        let input = "5 3\n3 2 N\nFRRFLLFFRRFLL\n0 3 W\nLLFFFLFLFL\n5 3\n0 3 W\nLLFFFLFLFL\n";
+        let output = drive_robots(split(input))
+            .flatten_to_iterator()
+            .collect::<Result<Vec<String>>>()?;
+
+        // The scent from the first grid doesn't save the last robot.
+        assert_eq!(output, vec!["3 3 N LOST", "2 3 S", "3 3 N LOST"]);
+
+        let errors = drive_robots(split("5 3\n1 1 E\nR\n5 60\n"))
+            .flatten_to_iterator()
+            .filter_map(|result| result.err())
+            .map(|e| e.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            errors,
+            vec!["line 4: grid coordinate exceeds maximum of 50"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn lost_only_skips_robots_that_stay_on_the_grid() -> Result<()> {
+        let input = r#"
+        5 3
+        1 1 E
+        RFRFRFRF
+        3 2 N
+        FRRFLLFFRRFLL
+        0 3 W
+        LLFFFLFLFL
+        "#;
+        let options = SimulationConfig {
+            lost_only: true,
+            ..SimulationConfig::default()
+        };
+        let output = drive_robots_with(split(input), &options)
+            .flatten_to_iterator()
+            .collect::<Result<Vec<String>>>()?;
+        assert_eq!(output, vec!["3 3 N LOST"]);
+        Ok(())
+    }
+
+    #[test]
+    fn robot_starting_outside_the_grid_produces_error() -> Result<()> {
+        let input = r#"
+        5 3
+        6 1 E
+        F
+        1 1 E
+        F
+        "#;
+        let output = drive_robots(split(input))
+            .flatten_to_iterator()
+            .map(|result| result.map_err(|e| e.to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            output,
+            vec![
+                Err("line 2: robot starts outside grid".to_owned()),
+                Ok("2 1 E".to_owned())
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn scents_are_where_robots_fell_off() -> Result<()> {
+        let input = "5 3\n1 1 E\nRFRFRFRF\n3 2 N\nFRRFLLFFRRFLL\n0 3 W\nLLFFFLFLFL\n";
+        let grid =
+            simulate_robots_buffered(input.as_bytes(), SimulationConfig::default(), |_| Ok(()))?;
+        assert_eq!(grid.scents(), vec![Coords { x: 3, y: 3 }]);
+        Ok(())
+    }
+
+    #[test]
+    fn scents_can_be_queried_by_cell() -> Result<()> {
+        let input = "5 3\n1 1 E\nRFRFRFRF\n3 2 N\nFRRFLLFFRRFLL\n0 3 W\nLLFFFLFLFL\n";
+        let grid =
+            simulate_robots_buffered(input.as_bytes(), SimulationConfig::default(), |_| Ok(()))?;
+        assert!(grid.has_scent_at(3, 3));
+        assert!(!grid.has_scent_at(2, 3));
+        assert!(!grid.has_scent_at(0, 3));
+        Ok(())
+    }
+
+    #[test]
+    fn forward_distance_behaves_like_repeated_f() -> Result<()> {
+        assert_eq!(
+            format!("{:?}", parse_instructions("F3R*2F2")?),
+            "[Forward(3), Turn(R), Forward(2), Forward(2)]"
+        );
+        assert_eq!(parse_instructions("*3F*2U")?, parse_instructions("FFFUU")?);
+
+        let mut grid: Grid = "5 3".parse()?;
+        let start: Robot = "3 1 N".parse()?;
+        let one_at_a_time = start
+            .clone()
+            .try_all_instructions(&grid, &parse_instructions("FFF")?);
+        let all_at_once = start
+            .clone()
+            .try_all_instructions(&grid, &parse_instructions("F3")?);
+        assert_eq!(one_at_a_time.unwrap_err().to_string(), "3 3 N");
+        assert_eq!(all_at_once.unwrap_err().to_string(), "3 3 N");
+
+        grid.apply_scent(&"3 3 N".parse()?);
+        let saved = start.try_all_instructions(&grid, &parse_instructions("F3")?);
+        assert_eq!(saved.unwrap().to_string(), "3 3 N");
+        Ok(())
+    }
+
+    #[test]
+    fn long_distances_count_towards_the_instruction_limit() -> Result<()> {
+        let macros = Macros::new();
+        assert!(parse_instruction_line("F99", &macros).is_ok());
+        for line in &["F2147483647", "F60F40", "*50F*2F25"] {
+            assert_eq!(
+                parse_instruction_line(line, &macros)
+                    .unwrap_err()
+                    .to_string(),
+                "instruction sequence exceeds 100 instructions"
+            );
+        }
+
+        // A robot that is saved by a scent stops trying.
+        let mut grid: Grid = "5 3".parse()?;
+        grid.apply_scent(&"3 3 N".parse()?);
+        let robot: Robot = "3 3 N".parse()?;
+        let saved = robot.try_next_instruction(&grid, &Instruction::Forward(i32::MAX));
+        assert_eq!(saved.unwrap().to_string(), "3 3 N");
+        Ok(())
+    }
+
+    #[test]
+    fn instructions_can_span_several_lines() -> Result<()> {
+        let input = r#"
+        5 3
+        1 1 E
+        RFRF
+        # The second half of the square
+        RFRF
+        3 2 N
+        FRRFLL
+        FFRRFLL
+        3 3
+        0 3 W
+        LLFFFLFLFL
+        "#;
+        let options = SimulationConfig {
+            multiline_instructions: true,
+            ..SimulationConfig::default()
+        };
+        let output = drive_robots_with(split(input), &options)
+            .flatten_to_iterator()
+            .collect::<Result<Vec<String>>>()?;
+        assert_eq!(output, vec!["1 1 E", "3 3 N LOST", "3 3 N LOST"]);
+
+        let mut buffered = vec![];
+        simulate_robots_buffered(input.as_bytes(), options, |outcome| {
+            buffered.push(format_outcome(&outcome?));
+            Ok(())
+        })?;
+        assert_eq!(buffered, output);
+        Ok(())
+    }
+
+    #[test]
+    fn stop_on_lost_turns_lost_robots_into_errors() -> Result<()> {
+        let input = r#"
+        5 3
+        1 1 E
+        RFRFRFRF
+        3 2 N
+        FRRFLLFFRRFLL
+        "#;
+        let options = SimulationConfig {
+            stop_on_lost: true,
+            ..SimulationConfig::default()
+        };
+        let mut output = drive_robots_with(split(input), &options).flatten_to_iterator();
+        assert_eq!(output.next().unwrap()?, "1 1 E");
+        assert_eq!(
+            output.next().unwrap().unwrap_err().to_string(),
+            "line 5: robot lost at 3 3 N"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn robots_can_be_compared_directly() -> Result<()> {
+        let grid: Grid = "5 3".parse()?;
+        let start = Robot {
+            coords: Coords { x: 1, y: 1 },
+            bearing: Bearing::E,
+            label: None,
+        };
+        let end = start.try_all_instructions(&grid, &parse_instructions("RFRFRFRF")?);
+        assert_eq!(
+            end,
+            Ok(Robot {
+                coords: Coords { x: 1, y: 1 },
+                bearing: Bearing::E,
+                label: None,
+            })
+        );
+        assert_eq!(
+            parse_instructions("LF")?,
+            vec![Instruction::Turn(Rotation::L), Instruction::F]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn will_be_lost_leaves_the_grid_alone() -> Result<()> {
+        let mut grid: Grid = "5 3".parse()?;
+        grid.apply_scent(&"0 3 N".parse()?);
+        let lost: Robot = "3 2 N".parse()?;
+        let safe: Robot = "1 1 E".parse()?;
+
+        for _ in 0..2 {
+            assert!(will_be_lost(
+                &grid,
+                &lost,
+                &parse_instructions("FRRFLLFFRRFLL")?
+            ));
+            assert!(!will_be_lost(
+                &grid,
+                &safe,
+                &parse_instructions("RFRFRFRF")?
+            ));
+        }
+        assert_eq!(grid.scents(), vec![Coords { x: 0, y: 3 }]);
+        Ok(())
+    }
+
+    #[test]
+    fn grids_can_have_negative_origins() -> Result<()> {
+        let grid: Grid = "-2 -2 2 2".parse()?;
+        assert_eq!(grid.to_string(), "-2 -2 2 2");
+        assert!(grid.contains(-2, 2));
+        assert!(!grid.contains(-3, 0));
+        assert_eq!("5 3".parse::<Grid>()?.to_string(), "5 3");
+
+        let input = r#"
+        -2 -2 2 2
+        0 0 S
+        FFF
+        0 0 S
+        FFFF
+        "#;
+        let output = drive_robots(split(input))
+            .flatten_to_iterator()
+            .collect::<Result<Vec<String>>>()?;
+        assert_eq!(output, vec!["0 -2 S LOST", "0 -2 S"]);
+
+        assert_eq!(
+            "2 2 -2 -2".parse::<Grid>().unwrap_err().to_string(),
+            "grid minimum must not be greater than its maximum"
+        );
+        assert_eq!(
+            "-51 0 2 2".parse::<Grid>().unwrap_err().to_string(),
+            "grid coordinate exceeds maximum of 50"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn paired_output_has_start_and_end() -> Result<()> {
+        let input = r#"
+        5 3
+        1 1 E
+        RFRFRFRF
+        3 2 N
+        FRRFLLFFRRFLL
+        0 3 W
+        LLFFFLFLFL
+        "#;
+        let pairs = drive_robots_paired(split(input), SimulationConfig::default())
+            .flatten_to_iterator()
+            .collect::<Result<Vec<_>>>()?;
+        let pairs = pairs
+            .iter()
+            .map(|(start, end, lost)| (start.to_string(), end.to_string(), *lost))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            pairs,
+            vec![
+                ("1 1 E".to_owned(), "1 1 E".to_owned(), false),
+                ("3 2 N".to_owned(), "3 3 N".to_owned(), true),
+                ("0 3 W".to_owned(), "2 3 S".to_owned(), false),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn crlf_input_gives_the_same_output_as_lf() -> Result<()> {
+        let lf = "5 3\n1 1 E\nRFRFRFRF\n\n3 2 N \nFRRFLLFFRRFLL\n0 3 W\nLLFFFLFLFL\n";
+        let crlf = lf.replace('\n', "\r\n");
+        let drive = |input: &str| {
+            let lines = input.split('\n').map(|l| Ok(l.to_owned()));
+            drive_robots(lines)
+                .flatten_to_iterator()
+                .collect::<Result<Vec<String>>>()
+        };
+        assert_eq!(drive(&crlf)?, drive(lf)?);
+        assert_eq!(drive(lf)?, vec!["1 1 E", "3 3 N LOST", "2 3 S"]);
+
+        assert_eq!("5 3\r".parse::<Grid>()?.to_string(), "5 3");
+        assert_eq!("1 1 E\r\n".parse::<Robot>()?.to_string(), "1 1 E");
+        assert_eq!(parse_instruction_line("RF\r", &Macros::new())?.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn runs_of_spaces_separate_fields() -> Result<()> {
+        assert_eq!("1  1  E".parse::<Robot>()?.to_string(), "1 1 E");
+        assert_eq!("5\t 3".parse::<Grid>()?.to_string(), "5 3");
+        assert_eq!(
+            "5  3  1".parse::<Grid>().unwrap_err().to_string(),
+            "grid line has too many fields"
+        );
+        assert_eq!(
+            "1 1 E  N".parse::<Robot>().unwrap_err().to_string(),
+            "position line has too many fields"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn rotations_can_be_given_in_degrees() -> Result<()> {
+        assert_eq!(parse_instructions("R180")?, parse_instructions("RR")?);
+        assert_eq!(parse_instructions("R270")?, parse_instructions("RRR")?);
+        assert_eq!(parse_instructions("L90F")?, parse_instructions("LF")?);
+        assert_eq!(parse_instructions("*2l180")?, parse_instructions("LLLL")?);
+        assert_eq!(parse_instructions("R*2F")?, parse_instructions("RFF")?);
+        assert_eq!(
+            parse_instructions("R45").unwrap_err().to_string(),
+            "rotation must be a multiple of 90 degrees"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn safe_forward_steps_counts_cells_to_the_edge() -> Result<()> {
+        let grid: Grid = "5 3".parse()?;
+        let steps =
+            |position: &str| -> Result<u32> { Ok(grid.safe_forward_steps(&position.parse()?)) };
+        assert_eq!(steps("1 1 N")?, 2);
+        assert_eq!(steps("1 1 E")?, 4);
+        assert_eq!(steps("1 1 S")?, 1);
+        assert_eq!(steps("1 1 W")?, 1);
+        assert_eq!(steps("0 3 N")?, 0);
+        assert_eq!(steps("5 0 E")?, 0);
+        assert_eq!(steps("4 0 S")?, 0);
+        assert_eq!(steps("0 2 W")?, 0);
+        assert_eq!(steps("1 1 NE")?, 2);
+        assert_eq!(steps("4 2 SW")?, 2);
+
+        let grid: Grid = "-2 -2 2 2".parse()?;
+        assert_eq!(grid.safe_forward_steps(&"0 0 S".parse()?), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn robots_without_instructions_produce_warnings() -> Result<()> {
+        let input = r#"
+        5 3
+        1 1 E
+        2 2 N
+        F
+        "#;
+        let options = SimulationConfig {
+            multiline_instructions: true,
+            warn_empty: true,
+            ..SimulationConfig::default()
+        };
+        let outcomes = simulate_robots(split(input), options.clone())
+            .flatten_to_iterator()
+            .collect::<Result<Vec<_>>>()?;
+        let output = outcomes.iter().map(format_outcome).collect::<Vec<_>>();
+        let warnings = outcomes
+            .iter()
+            .filter_map(|outcome| options.warning(outcome))
+            .collect::<Vec<_>>();
+
+        assert_eq!(output, vec!["1 1 E", "2 3 N"]);
+        assert_eq!(
+            warnings,
+            vec!["warning: robot starting at 1 1 E has no instructions"]
+        );
+        assert_eq!(SimulationConfig::default().warning(&outcomes[0]), None);
+        Ok(())
+    }
+
+    #[test]
+    fn parse_input_returns_every_robot() -> Result<()> {
+        let input = r#"
+        5 3
+        1 1 E
+        RFRFRFRF
+        3 2 N
+        FRRFLLFFRRFLL
+        0 3 W
+        LLFFFLFLFL
+        "#;
+        let (grid, robots) = parse_input(split(input))?;
+        assert_eq!(grid.to_string(), "5 3");
+        assert_eq!(robots.len(), 3);
+        assert_eq!(robots[0].0.bearing, Bearing::E);
+        assert_eq!(robots[0].1.len(), 8);
+        Ok(())
+    }
+
+    #[test]
+    fn lost_position_chooses_which_cell_to_report() -> Result<()> {
+        let input = r#"
+        5 3
+        3 2 N
+        FRRFLLFFRRFLL
+        3 3 E
+        F3
+        "#;
+        let drive = |lost_position| {
+            let options = SimulationConfig {
+                lost_position,
+                ..SimulationConfig::default()
+            };
+            drive_robots_with(split(input), &options)
+                .flatten_to_iterator()
+                .collect::<Result<Vec<String>>>()
+        };
+        assert_eq!(
+            drive(LostPosition::LastOnGrid)?,
+            vec!["3 3 N LOST", "5 3 E LOST"]
+        );
+        assert_eq!(
+            drive(LostPosition::Attempted)?,
+            vec!["3 4 N LOST", "6 3 E LOST"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn max_robots_stops_driving_after_the_limit() -> Result<()> {
+        let input = r#"
+        5 3
+        1 1 E
+        RFRFRFRF
+        3 2 N
+        FRRFLLFFRRFLL
+        0 3 W
+        LLFFFLFLFL
+        "#;
+        let options = SimulationConfig {
+            max_robots: Some(2),
+            ..SimulationConfig::default()
+        };
+        let output = drive_robots_with(split(input), &options)
+            .flatten_to_iterator()
+            .map(|result| result.map_err(|e| e.to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            output,
+            vec![
+                Ok("1 1 E".to_owned()),
+                Ok("3 3 N LOST".to_owned()),
+                Err("line 7: robot limit exceeded".to_owned()),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn y_down_makes_north_decrease_y() -> Result<()> {
+        let input = r#"
+        5 3
+        1 1 N
+        F
+        1 1 N
+        FF
+        3 2 E
+        RF
+        "#;
+        let drive = |y_down| {
+            let options = SimulationConfig {
+                y_down,
+                ..SimulationConfig::default()
+            };
+            drive_robots_with(split(input), &options)
+                .flatten_to_iterator()
+                .collect::<Result<Vec<String>>>()
+        };
+        assert_eq!(drive(false)?, vec!["1 2 N", "1 3 N", "3 1 S"]);
+        assert_eq!(drive(true)?, vec!["1 0 N", "1 0 N LOST", "3 3 S"]);
+
+        let mut grid: Grid = "5 3".parse()?;
+        grid.y_down = true;
+        assert_eq!(grid.safe_forward_steps(&"1 1 N".parse()?), 1);
+        assert_eq!(grid.safe_forward_steps(&"1 1 S".parse()?), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn parse_errors_can_be_matched() {
+        let error = drive_robots(split("")).err().unwrap();
+        assert!(matches!(
+            error.downcast_ref::<ParseError>(),
+            Some(ParseError::EmptyInput)
+        ));
+
+        let error = drive_robots(split("5 3\n1 1 X\nF\n"))
+            .flatten_to_iterator()
+            .find_map(Result::err)
+            .unwrap();
+        let line = error.downcast_ref::<LineError>().unwrap();
+        assert_eq!(line.line, 2);
+        assert_eq!(
+            line.error.downcast_ref::<ParseError>(),
+            Some(&ParseError::BadBearing)
+        );
+
+        let grid_error = |line: &str| Grid::try_from(line).err().unwrap().downcast::<ParseError>();
+        assert_eq!(grid_error("5").ok(), Some(ParseError::MissingY));
+        assert_eq!(grid_error("5 x").ok(), Some(ParseError::InvalidNumber));
+        assert_eq!(
+            grid_error("width=5 height=y").ok(),
+            Some(ParseError::InvalidNumber)
+        );
+        assert_eq!(grid_error(" ").ok(), Some(ParseError::BlankLine("grid")));
+        assert_eq!(
+            grid_error("-1 3").ok(),
+            Some(ParseError::NegativeCoordinate)
+        );
+        assert_eq!(
+            grid_error("51 3").ok(),
+            Some(ParseError::CoordinateTooLarge)
+        );
+        assert_eq!(grid_error("1 1 0 0").ok(), Some(ParseError::MinAboveMax));
+
+        let robot_error = |line: &str| {
+            Robot::try_from(line)
+                .err()
+                .unwrap()
+                .downcast::<ParseError>()
+        };
+        assert_eq!(robot_error("1").ok(), Some(ParseError::MissingY));
+        assert_eq!(robot_error("1 y N").ok(), Some(ParseError::InvalidNumber));
+        assert_eq!(robot_error("1 1").ok(), Some(ParseError::MissingBearing));
+        assert_eq!(
+            robot_error("1 1 N X").ok(),
+            Some(ParseError::TooManyFields("position"))
+        );
+    }
+
+    #[test]
+    fn summary_counts_robots_and_lost_robots() -> Result<()> {
+        let input = r#"
+        5 3
+        1 1 E
+        RFRFRFRF
+        3 2 N
+        FRRFLLFFRRFLL
+        0 3 W
+        LLFFFLFLFL
+        "#;
+        let mut summary = Summary::default();
+        for outcome in simulate_robots(split(input), SimulationConfig::default())? {
+            summary.add(&outcome?);
+        }
+        assert_eq!(summary, Summary { robots: 3, lost: 1 });
+        assert_eq!(summary.to_string(), "3 robots processed, 1 lost");
+        Ok(())
+    }
+
+    #[test]
+    fn labels_are_reported_with_their_robots() -> Result<()> {
+        let input = r#"
+        5 3
+        @rover1
+        1 1 E
+        RFRFRFRF
+        3 2 N
+        FRRFLLFFRRFLL
+        @ rover 3
+        0 3 W
+        LLFFFLFLFL
+        "#;
+        let output = join(drive_robots(split(input)).flatten_to_iterator())?;
+        let expected_output = format(
+            r#"
+            rover1: 1 1 E
+            3 3 N LOST
+            rover 3: 2 3 S
+            "#,
+        )?;
+        assert_eq!(output, expected_output);
+
+        let options = SimulationConfig {
+            multiline_instructions: true,
+            ..SimulationConfig::default()
+        };
+        let input = "5 3\n@rover1\n1 1 E\nRFRF\nRFRF\n@rover2\n3 2 N\nF\n";
+        let lines = input.lines().map(|l| Ok(l.to_owned()));
+        let output = drive_robots_with(lines, &options)?.collect::<Result<Vec<_>>>()?;
+        assert_eq!(output, vec!["rover1: 1 1 E", "rover2: 3 3 N"]);
+        Ok(())
+    }
+
+    #[test]
+    fn turn_and_advance_match_instructions() -> Result<()> {
+        let mut grid: Grid = "5 3".parse()?;
+        let robot: Robot = "1 1 E".parse()?;
+        let moved = robot
+            .clone()
+            .turn(&Rotation::L)
+            .advance(&grid)
+            .and_then(|robot| robot.advance(&grid));
+        assert_eq!(moved.map(|robot| robot.to_string()), Ok("1 3 N".into()));
+        let lost: Robot = "1 3 N".parse()?;
+        assert_eq!(lost.clone().advance(&grid), Err(lost.clone()));
+        grid.apply_scent(&lost);
+        assert_eq!(lost.clone().advance(&grid), Ok(lost));
+
+        for start in &["1 1 E", "0 0 S", "5 3 NE"] {
+            let robot: Robot = start.parse()?;
+            for rotation in &[Rotation::L, Rotation::R] {
+                let turned = robot.clone().turn(rotation);
+                assert_eq!(
+                    Some(turned),
+                    robot.destination(&grid, &Instruction::Turn(rotation.clone()))
+                );
+            }
+            for wrap in &[false, true] {
+                grid.wrap = *wrap;
+                let on_grid = robot
+                    .destination(&grid, &Instruction::F)
+                    .filter(|next| !next.is_out_of_bounds(&grid));
+                assert_eq!(robot.clone().advance(&grid).ok(), on_grid);
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn macros_expand_to_their_sequences() -> Result<()> {
+        let literal = r#"
+        5 3
+        1 1 E
+        RFRFRFRF
+        3 2 N
+        FRRFLLFFRRFLLFRFRFRFR
+        "#;
+        let defined = r#"
+        5 3
+        define SQUARE FRFRFRFR
+        define CIRCLE RFRF
+        1 1 E
+        *2CIRCLE
+        3 2 N
+        FRRFLLFFRRFLLSQUARE
+        "#;
+        assert_eq!(
+            join(drive_robots(split(defined)).flatten_to_iterator())?,
+            join(drive_robots(split(literal)).flatten_to_iterator())?
+        );
+
+        let errors = |input| {
+            drive_robots(split(input))
+                .flatten_to_iterator()
+                .filter_map(|result| result.err())
+                .map(|e| e.to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            errors("5 3\ndefine SQUARE FRFRFRFR\n1 1 E\nSQAURE\n"),
+            vec!["line 4: unknown macro SQAURE"]
+        );
+        assert_eq!(
+            errors("5 3\ndefine FRFR FRFR\n1 1 E\nF\n"),
+            vec![
+                "line 2: macro name must be capital letters, not starting with F, B, L, R, U or T"
+            ]
+        );
+        // Short enough to write, but too long once the macros are expanded.
+        assert_eq!(
+            errors("5 3\ndefine MANY FFFFFFFFFFFFFFFFFFFF\n1 1 N\nMANYMANYMANYMANYMANY\n"),
+            vec!["line 4: instruction sequence exceeds 100 instructions"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn max_steps_rejects_long_robots() -> Result<()> {
+        let options = SimulationConfig {
+            max_steps: Some(10),
+            ..SimulationConfig::default()
+        };
+        let input = "5 3\n1 1 E\nRFRFRFRF\n3 2 N\n*20R\n0 3 W\nF3F9\n";
+        let lines = input.lines().map(|l| Ok(l.to_owned()));
+        let output = drive_robots_with(lines, &options)?
+            .map(|result| result.unwrap_or_else(|e| e.to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            output,
+            vec![
+                "1 1 E",
+                "line 5: robot starting at 3 2 N takes more than 10 steps",
+                "line 7: robot starting at 0 3 W takes more than 10 steps",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn new_grid_is_the_same_as_a_parsed_one() -> Result<()> {
+        let parsed: Grid = "5 3".parse()?;
+        let new = Grid::new(5, 3);
+        assert_eq!(new.to_string(), parsed.to_string());
+        assert_eq!(new.scents(), vec![]);
+        for x in -1..=6 {
+            for y in -1..=4 {
+                assert_eq!(new.contains(x, y), parsed.contains(x, y));
+            }
+        }
+
+        let robot: Robot = "3 2 N".parse()?;
+        let instructions = parse_instructions("FRRFLLFFRRFLL")?;
+        assert_eq!(
+            robot.clone().try_all_instructions(&new, &instructions),
+            robot.try_all_instructions(&parsed, &instructions)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn robots_at_the_edge_of_i32_are_lost_rather_than_overflowing() {
+        let top = Coords { x: 0, y: i32::MAX };
+        let grid = Grid::with_bounds(
+            Coords {
+                x: 0,
+                y: i32::MAX - 2,
+            },
+            top.clone(),
+        );
+        let robot = Robot {
+            coords: top.clone(),
+            bearing: Bearing::N,
+            label: None,
+        };
+        assert_eq!(robot.clone().advance(&grid), Err(robot.clone()));
+        let lost = robot.clone().try_next_instruction(&grid, &Instruction::F);
+        assert_eq!(lost, Err(robot.clone()));
+
+        let fast = Robot {
+            coords: Coords {
+                x: 0,
+                y: i32::MAX - 2,
+            },
+            ..robot.clone()
+        };
+        let lost = fast.try_all_instructions(&grid, &[Instruction::Forward(i32::MAX)]);
+        assert_eq!(lost, Err(robot));
+    }
+
+    #[test]
+    fn position_without_instructions_at_end_produces_error() -> Result<()> {
+        let input = r#"
+        5 3
+        1 1 E
+        RFRFRFRF
+        3 2 N
+        "#;
+        let mut output = drive_robots(split(input))?;
+        assert_eq!(output.next().transpose()?, Some("1 1 E".to_owned()));
+        assert_eq!(
+            output.next().unwrap().unwrap_err().to_string(),
+            "line 4: robot position without instructions at end of input"
+        );
+        assert!(output.next().is_none());
+        assert_eq!(
+            check_robots(split(input)).unwrap_err().to_string(),
+            "line 4: robot position without instructions at end of input"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn count_robots_only_counts() -> Result<()> {
+        let input = r#"
+        5 3
+        1 1 E
+        RFRFRFRF
+        3 2 N
+        FRRFLLFFRRFLL
+        0 3 W
+        LLFFFLFLFL
+        "#;
+        let summary = count_robots(split(input), SimulationConfig::default())?;
+        assert_eq!(summary, Summary { robots: 3, lost: 1 });
+        Ok(())
+    }
+
+    #[test]
+    fn striding_robots_only_care_about_where_they_land() -> Result<()> {
+        let mut grid = "5 3".parse::<Grid>()?.with_stride(2);
+        grid.apply_scent(&"5 1 E".parse()?);
+
+        let robot: Robot = "1 1 E".parse()?;
+        let end = robot.try_all_instructions(&grid, &parse_instructions("FF")?);
+        assert_eq!(end.map(|r| r.to_string()), Ok("5 1 E".to_owned()));
+
+        // Jumping over the scented cell doesn't save it.
+        let robot: Robot = "4 1 E".parse()?;
+        let end = robot.try_all_instructions(&grid, &parse_instructions("F")?);
+        assert_eq!(end.map_err(|r| r.to_string()), Err("4 1 E".to_owned()));
+
+        assert_eq!(grid.safe_forward_steps(&"0 0 N".parse()?), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn chained_inputs_are_driven_as_one() -> Result<()> {
+        let grid = "5 3\n";
+        let robots = "1 1 E\nRFRFRFRF\n\n3 2 N\nFRRFLLFFRRFLL";
+        let more_robots = "0 3 W\nLLFFFLFLFL\n";
+        let sources = [grid, "", robots, more_robots].map(str::as_bytes);
+        let output = join(drive_robots(chain_lines(sources))?)?;
+        assert_eq!(output, "1 1 E\n3 3 N LOST\n2 3 S");
+        Ok(())
+    }
+
+    #[test]
+    fn ghosts_carry_on_off_the_grid() -> Result<()> {
+        let input = "5 3\n1 1 W\nFFFF\n3 2 N\nFRRFLLFFRRFLL\n";
+        let options = SimulationConfig {
+            ghost: true,
+            ..SimulationConfig::default()
+        };
+        let lines = input.lines().map(|l| Ok(l.to_owned()));
+        let output = drive_robots_with(lines, &options)?.collect::<Result<Vec<_>>>()?;
+        assert_eq!(output, vec!["-3 1 W", "3 3 N"]);
+        Ok(())
+    }
+
+    #[test]
+    fn verbose_reports_how_many_instructions_ran() -> Result<()> {
+        let input = "5 3\n1 1 E\nRFRFRFRF\n3 2 N\nFRRFLLFFRRFLL\n";
+        let options = SimulationConfig {
+            verbose: true,
+            ..SimulationConfig::default()
+        };
+        let lines = input.lines().map(|l| Ok(l.to_owned()));
+        let output = drive_robots_with(lines, &options)?.collect::<Result<Vec<_>>>()?;
+        assert_eq!(
+            output,
+            vec!["1 1 E (executed 8 of 8)", "3 3 N LOST (executed 7 of 13)"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn scent_density_is_scented_cells_over_area() -> Result<()> {
+        let input = "5 3\n1 1 E\nRFRFRFRF\n3 2 N\nFRRFLLFFRRFLL\n0 3 W\nLLFFFLFLFL\n";
+        let grid =
+            simulate_robots_buffered(input.as_bytes(), SimulationConfig::default(), |_| Ok(()))?;
+        assert_eq!(grid.area(), 24);
+        assert_eq!(grid.scent_density(), 1.0 / 24.0);
+        assert_eq!("-1 -1 1 1".parse::<Grid>()?.area(), 9);
+        Ok(())
+    }
+
+    #[test]
+    fn commas_and_spaces_between_instructions_are_ignored() -> Result<()> {
+        let bare = "5 3\n1 1 E\nRFRFRFRF\n3 2 N\nFRRFLLFFRRFLL\n0 3 W\n*2LFFFLFLFL\n";
+        let separated =
+            "5 3\n1 1 E\nR,F,R,F,R,F,R,F\n3 2 N\nF, R, R, F, L, L, F, F, R, R, F, L, L\n0 3 W\n*2L,F,F,F,L,F,L,F,L\n";
+        let drive = |input: &str| {
+            let lines = input.lines().map(|l| Ok(l.to_owned()));
+            drive_robots(lines)?.collect::<Result<Vec<_>>>()
+        };
+        assert_eq!(drive(separated)?, drive(bare)?);
+        assert_eq!(
+            parse_instructions("T1,2 , F")?,
+            parse_instructions("T1,2F")?
+        );
+        assert_eq!(
+            parse_instructions("F,X").unwrap_err().to_string(),
+            "instruction must be F, B, L, R, U, S, H, or Tx,y"
+        );
+        assert_eq!(
+            parse_instructions("*2,F").unwrap_err().to_string(),
+            "repeat count must be followed by an instruction"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn extent_includes_where_lost_robots_fell() -> Result<()> {
+        let input = r#"
+        5 3
+        1 1 E
+        RFRFRFRF
+        3 2 N
+        FRRFLLFFRRFLL
+        0 3 W
+        LLFFFLFLFL
+        "#;
+        let mut extents = simulate_robots(split(input), SimulationConfig::default())?
+            .map(|outcome| Ok(outcome?.extent))
+            .collect::<Result<Vec<_>>>()?
+            .into_iter();
+        let mut extent = extents.next().unwrap();
+        assert_eq!(extent.to_string(), "0 0 1 1");
+        for other in extents {
+            extent.merge(&other);
+        }
+        assert_eq!(extent.to_string(), "0 0 3 4");
+        Ok(())
+    }
+
+    #[test]
+    fn borrowed_lines_convert_without_allocating() -> Result<()> {
+        let line = "5 3";
+        let grid = Grid::try_from(line)?;
+        assert_eq!(grid.max, Coords { x: 5, y: 3 });
+        let robot: Robot = "1 1 E".try_into()?;
+        assert_eq!(robot.to_string(), "1 1 E");
+        assert!(Robot::try_from("1 1 X").is_err());
+        assert!(Grid::try_from("5 -1").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn default_grid_is_used_when_input_starts_with_a_robot() -> Result<()> {
+        let input = r#"
+        1 1 E
+        RFRFRFRF
+        3 2 N
+        FRRFLLFFRRFLL
+        "#;
+        let options = SimulationConfig {
+            default_grid: Some(Coords { x: 5, y: 3 }),
+            ..SimulationConfig::default()
+        };
+        let output = drive_robots_with(split(input), &options)?;
+        assert_eq!(join(output)?, "1 1 E\n3 3 N LOST");
+
+        let output = drive_robots_with(split("2 2\n2 2 N\nFF"), &options)?;
+        assert_eq!(join(output)?, "2 2 N LOST");
+
+        assert!(drive_robots(split(input)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn rotated_by_matches_repeated_rotations() {
+        use Bearing::*;
+        assert_eq!(N.rotated_by(5), N.quarter_turn(&Rotation::R));
+        assert_eq!(N.rotated_by(-1), N.quarter_turn(&Rotation::L));
+        assert_eq!(NE.rotated_by(-6), SW);
+        for bearing in [N, NE, E, SE, S, SW, W, NW] {
+            let mut left = bearing;
+            for turns in 0..10 {
+                assert_eq!(bearing.rotated_by(-turns), left);
+                left = left.quarter_turn(&Rotation::L);
+            }
+        }
+    }
+
+    #[test]
+    fn results_can_be_joined_on_one_line() -> Result<()> {
+        let input = r#"
+        5 3
+        1 1 E
+        RFRFRFRF
+        3 2 N
+        FRRFLLFFRRFLL
+        0 3 W
+        LLFFFLFLFL
+        "#;
+        let output = join_on_one_line(drive_robots(split(input))?)?;
+        assert_eq!(output, "1 1 E; 3 3 N LOST; 2 3 S");
+        Ok(())
+    }
+
+    #[test]
+    fn faded_scents_dont_save_robots() -> Result<()> {
+        let input = r#"
+        5 3
+        3 2 N
+        FRRFLLFFRRFLL
+        3 2 N
+        FRRFLLFFRRFLL
+        3 2 N
+        FRRFLLFFRRFLL
+        "#;
+        let output = drive_robots(split(input))?;
+        assert_eq!(join(output)?, "3 3 N LOST\n3 2 N\n3 2 N");
+
+        let options = SimulationConfig {
+            scent_uses: Some(1),
+            ..SimulationConfig::default()
+        };
+        let output = drive_robots_with(split(input), &options)?;
+        assert_eq!(join(output)?, "3 3 N LOST\n3 2 N\n3 3 N LOST");
+
+        // Tracing only asks what the robots would do.
+        let options = options.with_trace(true);
+        let output = drive_robots_with(split(input), &options)?;
+        assert_eq!(join(output)?, "3 3 N LOST\n3 2 N\n3 3 N LOST");
+        Ok(())
+    }
+
+    #[test]
+    fn asking_about_faded_scents_doesnt_use_them_up() -> Result<()> {
+        let mut grid = "5 3".parse::<Grid>()?.with_scent_policy(Fading::new(1));
+        grid.apply_scent(&"3 3 N".parse()?);
+        let robot: Robot = "3 2 N".parse()?;
+        let instructions = parse_instructions("FRRFLLFFRRFLL")?;
+
+        for _ in 0..2 {
+            assert!(!will_be_lost(&grid, &robot, &instructions));
+            let trace = trace_robot(&grid, robot.clone(), &instructions);
+            assert_eq!(trace.last().map(Robot::to_string), Some("3 2 N".into()));
+            let analysis = analyze_instructions(&grid, robot.clone(), &instructions);
+            assert!(analysis.safe);
+        }
+        assert!(grid.has_scent_at(3, 3));
+        Ok(())
+    }
+
+    #[test]
+    fn default_grid_is_a_single_unscented_cell() {
+        let grid = Grid::default();
+        assert_eq!(grid.scents(), vec![]);
+        assert_eq!(grid.area(), 1);
+        assert!(grid.contains(0, 0));
+        assert!(!grid.contains(1, 0));
+    }
+
+    #[test]
+    fn s_leaves_a_scent_without_moving() -> Result<()> {
+        let input = r#"
+        5 3
+        3 2 N
+        FS
+        3 3 N
+        F
+        "#;
+        let output = drive_robots(split(input))?;
+        assert_eq!(join(output)?, "3 3 N\n3 3 N");
+        assert_eq!(parse_instructions("SS")?, vec![Instruction::Scent; 2]);
+        Ok(())
+    }
+
+    #[test]
+    fn config_can_be_built_from_the_default() -> Result<()> {
+        let config = SimulationConfig::default()
+            .with_lost_marker("GONE")
+            .with_max_robots(1);
+        let output = drive_robots_with(split("5 3\n3 2 N\nFRRFLLFFRRFLL\n1 1 E\nF"), &config)?;
+        assert_eq!(
+            output
+                .map(|r| r.map_err(|e| e.to_string()))
+                .collect::<Vec<_>>(),
+            vec![
+                Ok("3 3 N GONE".to_owned()),
+                Err("line 5: robot limit exceeded".to_owned())
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn turning_on_the_spot_skips_the_moves() -> Result<()> {
+        let grid: Grid = "5 3".parse()?;
+        let start: Robot = "1 1 NE".parse()?;
+        let instructions = parse_instructions("RRRLRL")?;
+        let fast = start.clone().try_all_instructions(&grid, &instructions);
+        let slow = instructions.iter().try_fold(start, |robot, instruction| {
+            robot.try_next_instruction(&grid, instruction)
+        });
+        assert_eq!(fast, slow);
+        assert_eq!(fast.map(|r| r.to_string()), Ok("1 1 SW".to_owned()));
+
+        let options = SimulationConfig::default().with_verbose(true);
+        let input = "5 3\n1 1 NE\nRRRLRL\n1 1 E\nRRRLRLHF";
+        let output = drive_robots_with(split(input), &options)?;
+        assert_eq!(
+            join(output)?,
+            "1 1 SW (executed 6 of 6)\n1 1 W (executed 6 of 8)"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn bearings_can_be_printed_as_arrows() -> Result<()> {
+        use Bearing::*;
+        let arrows: String = [N, NE, E, SE, S, SW, W, NW]
+            .iter()
+            .map(|b| b.arrow())
+            .collect();
+        assert_eq!(arrows, "↑↗→↘↓↙←↖");
+
+        let config = SimulationConfig::default().with_arrows(true);
+        let output =
+            drive_robots_with(split("5 3\n1 1 E\nRFRFRFRF\n3 2 N\nFRRFLLFFRRFLL"), &config)?;
+        assert_eq!(join(output)?, "1 1 →\n3 3 ↑ LOST");
+
+        let grid: Grid = "0 0".parse()?;
+        for bearing in [N, NE, E, SE, S, SW, W, NW] {
+            let robot = Robot {
+                coords: Coords { x: 0, y: 0 },
+                bearing,
+                label: None,
+            };
+            assert_eq!(
+                render_grid(&grid, &[robot]),
+                format!("{}\n", bearing.arrow())
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn analysis_finds_the_step_that_falls_off() -> Result<()> {
+        let grid: Grid = "5 3".parse()?;
+        let start: Robot = "3 2 N".parse()?;
+        let analysis =
+            analyze_instructions(&grid, start.clone(), &parse_instructions("FRRFLLFFRRFLL")?);
+        assert_eq!(
+            analysis,
+            Analysis {
+                safe: false,
+                lost_at_step: Some(7)
+            }
+        );
+
+        let analysis = analyze_instructions(&grid, start, &parse_instructions("FRRF")?);
+        assert!(analysis.safe);
+        assert_eq!(analysis.lost_at_step, None);
+        Ok(())
+    }
+
+    #[test]
+    fn grid_size_can_be_given_as_width_and_height() -> Result<()> {
+        let grid: Grid = "width=5 height=3".parse()?;
+        assert_eq!(grid.to_string(), "5 3");
+        let grid: Grid = "height=3 width=5".parse()?;
+        assert_eq!(grid.to_string(), "5 3");
+
+        let error = |line: &str| line.parse::<Grid>().err().unwrap().to_string();
+        assert_eq!(error("width=5 depth=3"), "unknown grid key depth");
+        assert_eq!(error("width=5 width=3"), "grid key width is given twice");
+        assert_eq!(
+            error("width=5 3"),
+            "grid line must be all numbers or all key=value"
+        );
+        assert_eq!(error("width=5"), "missing y coordinate");
+        assert_eq!(
+            error("width=-5 height=3"),
+            "grid coordinate must not be negative"
+        );
+
+        let output = drive_robots(split("width=5 height=3\n3 2 N\nFRRFLLFFRRFLL"))?;
+        assert_eq!(join(output)?, "3 3 N LOST");
+        Ok(())
+    }
+
+    #[test]
+    fn h_ignores_the_rest_of_the_instructions() -> Result<()> {
+        let grid: Grid = "5 3".parse()?;
+        let start: Robot = "1 1 N".parse()?;
+        let instructions = parse_instructions("FHF")?;
+        assert_eq!(instructions[1], Instruction::Halt);
+        let end = start.clone().try_all_instructions(&grid, &instructions);
+        assert_eq!(end.map(|r| r.to_string()), Ok("1 2 N".to_owned()));
+        let trace = trace_robot(&grid, start, &instructions);
+        let trace = trace.iter().map(Robot::to_string).collect::<Vec<_>>();
+        assert_eq!(trace, vec!["1 2 N", "1 2 N", "1 2 N"]);
+
+        let options = SimulationConfig::default().with_verbose(true);
+        let input = "5 3\n1 1 N\nFHF\n1 2 N\nFHFFFF\n0 3 N\nRLFFHF";
+        let output = drive_robots_with(split(input), &options)?;
+        assert_eq!(
+            join(output)?,
+            "1 2 N (executed 1 of 3)\n1 3 N (executed 1 of 6)\n0 3 N LOST (executed 2 of 6)"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn distance_is_from_start_to_reported_end() -> Result<()> {
+        let input = r#"
+        5 3
+        1 1 E
+        RFRFRFRF
+        3 2 N
+        FRRFLLFFRRFLL
+        0 3 W
+        LLFFFLFLFL
+        "#;
+        let options = SimulationConfig::default().with_distance(true);
+        let output = drive_robots_with(split(input), &options)?;
+        assert_eq!(
+            join(output)?,
+            "1 1 E (dist 0)\n3 3 N LOST (dist 1)\n2 3 S (dist 2)"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn blank_lines_before_the_grid_are_skipped() -> Result<()> {
+        let lines = vec!["   ", "\t", "5 3", "1 1 E", "F"];
+        let output = drive_robots(lines.into_iter().map(|l| Ok(l.to_owned())))?;
+        assert_eq!(join(output)?, "2 1 E");
+
+        let blank = drive_robots(vec![Ok("  ".to_owned())].into_iter());
+        assert_eq!(blank.err().unwrap().to_string(), "input must not be empty");
+        assert_eq!(
+            "  ".parse::<Grid>().err().unwrap().to_string(),
+            "grid line is blank"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn cleared_scents_save_nobody() -> Result<()> {
+        let mut grid: Grid = "5 3".parse()?;
+        let robot: Robot = "3 3 N".parse()?;
+        grid.apply_scent(&robot);
+        let saved = robot.clone().try_next_instruction(&grid, &Instruction::F);
+        assert_eq!(saved, Ok(robot.clone()));
+
+        grid.clear_scents();
+        assert_eq!(grid.scents(), vec![]);
+        let lost = robot.clone().try_next_instruction(&grid, &Instruction::F);
+        assert_eq!(lost, Err(robot));
+        Ok(())
+    }
+
+    #[test]
+    fn bearings_can_be_written_as_words() -> Result<()> {
+        assert_eq!(Bearing::try_from("N")?, Bearing::N);
+        assert_eq!(Bearing::try_from("north")?, Bearing::N);
+        assert_eq!(Bearing::try_from("East")?, Bearing::E);
+        assert_eq!(Bearing::try_from("SOUTH")?, Bearing::S);
+        assert_eq!(Bearing::try_from("west")?, Bearing::W);
+        assert!(Bearing::try_from("nor").is_err());
+
+        let robot: Robot = "1 1 North".parse()?;
+        assert_eq!(robot.to_string(), "1 1 N");
+        Ok(())
+    }
+
+    #[test]
+    fn strict_mode_only_accepts_the_challenge_layout() -> Result<()> {
+        let drive = |input: &str, strict| -> Result<String> {
+            let lines = input.lines().map(|l| Ok(l.to_owned()));
+            let options = SimulationConfig::default().with_strict(strict);
+            join(drive_robots_with(lines, &options)?)
+        };
+        let canonical = "5 3\n1 1 E\nRFRFRFRF\n\n3 2 N\nFRRFLLFFRRFLL\n\n0 3 W\nLLFFFLFLFL\n";
+        assert_eq!(drive(canonical, true)?, "1 1 E\n3 3 N LOST\n2 3 S");
+
+        let stray = "5 3\n1 1 E\n\nRFRFRFRF\n";
+        assert_eq!(drive(stray, false)?, "1 1 E");
+        assert_eq!(
+            drive(stray, true).err().unwrap().to_string(),
+            "line 3: strict mode: unexpected blank line"
+        );
+
+        let error = |input| drive(input, true).err().unwrap().to_string();
+        assert_eq!(
+            error("\n5 3\n"),
+            "line 1: strict mode: unexpected blank line"
+        );
+        assert_eq!(
+            error("5 3\n1 1 E\nF\n\n\n"),
+            "line 5: strict mode: unexpected blank line"
+        );
+        assert_eq!(
+            error("5 3\n# hi\n"),
+            "line 2: strict mode: unexpected comment"
+        );
+        assert_eq!(
+            error("5  3\n"),
+            "line 1: strict mode: unexpected whitespace"
+        );
+        assert_eq!(
+            error("5 3\n1 1 E \nF\n"),
+            "line 2: strict mode: unexpected whitespace"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn outcomes_can_start_with_their_position_line_number() -> Result<()> {
+        let input =
+            "# robots\n5 3\n1 1 E\nRFRFRFRF\n\n3 2 N\nFRRFLLFFRRFLL\n\n# last\n0 3 W\nLLFFFLFLFL\n";
+        let lines = || input.lines().map(|l| Ok(l.to_owned()));
+        let options = SimulationConfig::default().with_line_numbers(true);
+        let output = drive_robots_with(lines(), &options)?;
+        assert_eq!(join(output)?, "L3: 1 1 E\nL6: 3 3 N LOST\nL10: 2 3 S");
+
+        let multiline = options.with_multiline_instructions(true);
+        let output = drive_robots_with(lines(), &multiline)?;
+        assert_eq!(join(output)?, "L3: 1 1 E\nL6: 3 3 N LOST\nL10: 2 3 S");
+        Ok(())
+    }
+
+    #[test]
+    fn outcomes_can_echo_their_instructions() -> Result<()> {
+        let input = r#"
+        5 3
+        define DUO FF
+        1 1 E
+        RFRFRFRF
+        3 2 N
+        FRRFLLFFRRFLL
+        0 3 W
+        LLDUOFLFLFL
+        "#;
+        let options = SimulationConfig::default().with_echo_instructions(true);
+        let output = drive_robots_with(split(input), &options)?;
+        assert_eq!(
+            join(output)?,
+            "1 1 E  <= RFRFRFRF\n3 3 N LOST  <= FRRFLLFFRRFLL\n2 3 S  <= LLDUOFLFLFL"
+        );
+
+        let input = "5 3\n1 1 E\nRFRF\nRFRF\n";
+        let multiline = options.with_multiline_instructions(true);
+        let output = drive_robots_with(split(input), &multiline)?;
+        assert_eq!(join(output)?, "1 1 E  <= RFRFRFRF");
+        Ok(())
+    }
+
+    #[test]
+    fn clamping_finds_the_nearest_cell_on_the_grid() -> Result<()> {
+        let grid: Grid = "5 3".parse()?;
+        assert_eq!(grid.clamp(2, 1), (2, 1));
+        assert_eq!(grid.clamp(-2, 1), (0, 1));
+        assert_eq!(grid.clamp(7, 2), (5, 2));
+        assert_eq!(grid.clamp(3, -4), (3, 0));
+        assert_eq!(grid.clamp(2, 9), (2, 3));
+        assert_eq!(grid.clamp(8, -1), (5, 0));
+        assert_eq!(grid.clamp(i32::MIN, i32::MAX), (0, 3));
+
+        let offset = Grid::with_bounds(Coords { x: -2, y: -1 }, Coords { x: 2, y: 1 });
+        assert_eq!(offset.clamp(-5, -5), (-2, -1));
+        assert_eq!(offset.clamp(-1, 0), (-1, 0));
+        Ok(())
+    }
+}
@@ -1,356 +1,444 @@
-mod flatten;
-
-use crate::flatten::ResultOfIteratorOfResult;
-use anyhow::{bail, Error, Result};
-use enum_display_derive::Display;
-use itertools::Itertools;
-use std::collections::HashSet;
-use std::convert::{TryFrom, TryInto};
-use std::fmt::Display;
-use std::io::{self, BufRead};
-
-#[derive(Clone, Copy, Debug, Display)]
-enum Bearing {
-    N,
-    E,
-    S,
-    W,
+use anyhow::{bail, Context, Result};
+use redbadger_challenge::{
+    chain_lines, check_robots, count_robots, generate, join_on_one_line, render_grid, scent_diff,
+    simulate_robots_buffered, Coords, Extent, Grid, LostPosition, OutputFormat, ParseError,
+    RobotOutcome, SimulationConfig, Summary,
+};
+use std::env;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Cursor, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+
+/// Whether to print lost robots in red and the others in green.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum Color {
+    /// Only if stdout is a terminal.
+    #[default]
+    Auto,
+    Always,
+    Never,
 }
 
-impl TryFrom<&str> for Bearing {
-    type Error = anyhow::Error;
-
-    fn try_from(input: &str) -> Result<Self, Self::Error> {
-        use Bearing::*;
-        match input {
-            "N" => Ok(N),
-            "E" => Ok(E),
-            "S" => Ok(S),
-            "W" => Ok(W),
-            _ => Err(Error::msg("Bearing must be one of N, E, S, or W")),
-        }
-    }
-}
-
-impl Bearing {
-    fn rotate(self, rotation: &Rotation) -> Bearing {
-        use Bearing::*;
-        use Rotation::*;
-
-        match (self, rotation) {
-            (N, L) => W,
-            (E, L) => N,
-            (S, L) => E,
-            (W, L) => S,
-            (W, R) => N,
-            (N, R) => E,
-            (E, R) => S,
-            (S, R) => W,
+impl Color {
+    fn parse(value: &str) -> Result<Color> {
+        match value {
+            "auto" => Ok(Color::Auto),
+            "always" => Ok(Color::Always),
+            "never" => Ok(Color::Never),
+            _ => bail!("--color must be auto, always or never"),
         }
     }
 }
 
-#[derive(Debug)]
-enum Rotation {
-    L,
-    R,
+/// Wraps a line of text output in the ANSI codes for red or green.
+fn colorize(line: &str, lost: bool) -> String {
+    let code = if lost { 31 } else { 32 };
+    format!("\x1b[{}m{}\x1b[0m", code, line)
 }
 
-#[derive(Debug)]
-enum Instruction {
-    F,
-    Turn(Rotation),
+#[derive(Debug, Default)]
+struct Args {
+    /// Read from these files, one after the other, instead of stdin.
+    inputs: Vec<PathBuf>,
+    /// Report bad robots on stderr and carry on with the rest.
+    keep_going: bool,
+    /// Draw the grid after all of the robots have finished.
+    render: bool,
+    /// Only check that the input is valid.
+    check: bool,
+    /// Only print how many robots there were, and how many got lost.
+    count_only: bool,
+    /// Print each robot's result as soon as it is typed in, and carry on
+    /// after errors.
+    repl: bool,
+    /// List the scents after all of the robots have finished.
+    scents: bool,
+    /// Print a box around everywhere that the robots went, at the end.
+    extent: bool,
+    /// List the scents that running this other input doesn't leave.
+    scent_diff: Option<PathBuf>,
+    /// Print a random input with this many robots, instead of reading one.
+    generate: Option<usize>,
+    /// The seed for generate.
+    seed: u64,
+    /// Finish with a count of robots on stderr.
+    summary: bool,
+    /// Print that count on stdout instead, after the robots.
+    summary_on_stdout: bool,
+    /// Print all of the robots on one line, without their traces.
+    oneline: bool,
+    /// Drive the robots on each grid on a separate thread.
+    parallel: bool,
+    color: Color,
+    options: SimulationConfig,
 }
 
-impl TryFrom<char> for Instruction {
-    type Error = anyhow::Error;
-
-    fn try_from(value: char) -> Result<Self, Self::Error> {
-        use Instruction::*;
-        use Rotation::*;
-        match value {
-            'F' => Ok(F),
-            'L' => Ok(Turn(L)),
-            'R' => Ok(Turn(R)),
-            _ => Err(Error::msg("instruction must be F, L, or R")),
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args> {
+    let mut parsed = Args::default();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            #[cfg(feature = "json")]
+            "--json" => parsed.options.format = OutputFormat::Json,
+            "--keep-going" => parsed.keep_going = true,
+            "--collisions" => parsed.options.collisions = true,
+            "--render" => parsed.render = true,
+            "--check" => parsed.check = true,
+            "--count-only" => parsed.count_only = true,
+            "--repl" => parsed.repl = true,
+            "--scents" => parsed.scents = true,
+            "--extent" => parsed.extent = true,
+            "--summary" => parsed.summary = true,
+            "--summary-on-stdout" => parsed.summary_on_stdout = true,
+            "--oneline" => parsed.oneline = true,
+            "--parallel" if cfg!(feature = "parallel") => parsed.parallel = true,
+            "--trace" => parsed.options.trace = true,
+            "--quiet" | "--lost-only" => parsed.options.lost_only = true,
+            "--multiline" => parsed.options.multiline_instructions = true,
+            "--stop-on-lost" => parsed.options.stop_on_lost = true,
+            "--warn-empty" => parsed.options.warn_empty = true,
+            "--y-down" => parsed.options.y_down = true,
+            "--ghost" => parsed.options.ghost = true,
+            "--eight-way" => parsed.options.eight_way = true,
+            "--verbose" => parsed.options.verbose = true,
+            "--arrows" => parsed.options.arrows = true,
+            "--distance" => parsed.options.distance = true,
+            "--strict" => parsed.options.strict = true,
+            "--with-line-numbers" => parsed.options.line_numbers = true,
+            "--echo-instructions" => parsed.options.echo_instructions = true,
+            "--report-attempted" => parsed.options.lost_position = LostPosition::Attempted,
+            "--lost-marker" => {
+                parsed.options.lost_marker = args
+                    .next()
+                    .context("--lost-marker must be followed by a word")?;
+            }
+            "--generate" => {
+                let robots = args.next().and_then(|n| n.parse().ok());
+                parsed.generate =
+                    Some(robots.context("--generate must be followed by a number of robots")?);
+            }
+            "--max-robots" => {
+                let max = args.next().and_then(|n| n.parse().ok());
+                parsed.options.max_robots =
+                    Some(max.context("--max-robots must be followed by a number")?);
+            }
+            "--timeout-steps" => {
+                let max = args.next().and_then(|n| n.parse().ok());
+                parsed.options.max_steps =
+                    Some(max.context("--timeout-steps must be followed by a number")?);
+            }
+            "--stride" => {
+                let stride = args.next().and_then(|n| n.parse().ok()).filter(|&n| n > 0);
+                parsed.options.stride =
+                    stride.context("--stride must be followed by a positive number")?;
+            }
+            "--default-grid" => {
+                let size = args.next().and_then(|size| {
+                    let (x, y) = size.split_once('x')?;
+                    // Checked in the same way as a grid line.
+                    format!("{} {}", x, y).parse::<Grid>().ok()?;
+                    Some(Coords {
+                        x: x.parse().ok()?,
+                        y: y.parse().ok()?,
+                    })
+                });
+                parsed.options.default_grid =
+                    Some(size.context("--default-grid must be followed by a size like 10x10")?);
+            }
+            "--scent-uses" => {
+                let uses = args.next().and_then(|n| n.parse().ok());
+                parsed.options.scent_uses =
+                    Some(uses.context("--scent-uses must be followed by a number")?);
+            }
+            "--seed" => {
+                let seed = args.next().and_then(|n| n.parse().ok());
+                parsed.seed = seed.context("--seed must be followed by a number")?;
+            }
+            "--scent-diff" => {
+                let path = args
+                    .next()
+                    .context("--scent-diff must be followed by a file name")?;
+                parsed.scent_diff = Some(path.into());
+            }
+            "--color" => {
+                let value = args
+                    .next()
+                    .context("--color must be followed by auto, always or never")?;
+                parsed.color = Color::parse(&value)?;
+            }
+            _ if arg.starts_with("--color=") => {
+                parsed.color = Color::parse(&arg["--color=".len()..])?;
+            }
+            "--input" => {
+                let path = args
+                    .next()
+                    .context("--input must be followed by a file name")?;
+                parsed.inputs.push(path.into());
+            }
+            _ if !arg.starts_with('-') => parsed.inputs.push(arg.into()),
+            _ => bail!("unrecognised argument: {}", arg),
         }
     }
+    Ok(parsed)
 }
 
-#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
-struct Coords {
-    x: i32,
-    y: i32,
-}
-
-impl<'a> Coords {
-    fn try_from_iterator(split: &mut impl Iterator<Item = &'a str>) -> Result<Self, anyhow::Error> {
-        let x = split
-            .next()
-            .ok_or_else(|| Error::msg("missing x coordinate"))?
-            .parse::<i32>()?;
-        let y = split
-            .next()
-            .ok_or_else(|| Error::msg("missing y coordinate"))?
-            .parse::<i32>()?;
-        Ok(Coords { x, y })
+fn open_input(args: &Args) -> Result<Box<dyn BufRead>> {
+    match args.inputs.as_slice() {
+        [] => Ok(Box::new(io::stdin().lock())),
+        [path] => open_file(path),
+        paths => {
+            // Joined line by line, so that a file with no newline at the
+            // end doesn't run into the next one.
+            let sources = paths
+                .iter()
+                .map(|p| open_file(p))
+                .collect::<Result<Vec<_>>>()?;
+            let mut joined = String::new();
+            for line in chain_lines(sources) {
+                joined.push_str(&line?);
+                joined.push('\n');
+            }
+            Ok(Box::new(Cursor::new(joined)))
+        }
     }
 }
 
-// The grid looks like this:
-//     y (North)
-//     ^
-//     |
-//     +-------> x (East)
-// If a robot falls off the edge then we add {x, y} to scents.
-#[derive(Debug)]
-struct Grid {
-    max: Coords,
-    scents: HashSet<Coords>,
+fn open_file(path: &Path) -> Result<Box<dyn BufRead>> {
+    let file = File::open(path).with_context(|| format!("could not open {}", path.display()))?;
+    Ok(Box::new(BufReader::new(file)))
 }
 
-impl TryFrom<String> for Grid {
-    type Error = anyhow::Error;
-
-    fn try_from(size_line: String) -> Result<Self, Self::Error> {
-        let mut split = size_line.split(' ');
-        let max = Coords::try_from_iterator(&mut split)?;
-        if let Some(_) = split.next() {
-            bail!("grid line has too many fields");
-        }
-        Ok(Grid {
-            max,
-            scents: Default::default(),
-        })
+fn run(input: impl BufRead, args: Args, mut output: impl Write) -> Result<()> {
+    if args.check {
+        let lines = input.lines().map(|l| Ok(l?));
+        writeln!(output, "{}", check_robots(lines)?)?;
+        return Ok(());
     }
-}
-
-impl Grid {
-    fn has_scent(&self, robot: &Robot) -> bool {
-        self.scents.contains(&robot.coords)
+    if args.repl {
+        return repl(input, args.options, output);
     }
-
-    fn apply_scent(&mut self, robot: &Robot) {
-        self.scents.insert(robot.coords.clone());
+    if args.count_only {
+        let lines = input.lines().map(|l| Ok(l?));
+        writeln!(output, "{}", count_robots(lines, args.options)?)?;
+        return Ok(());
     }
-}
-
-#[derive(Clone, Debug)]
-struct Robot {
-    coords: Coords,
-    bearing: Bearing,
-}
-
-impl TryFrom<String> for Robot {
-    type Error = anyhow::Error;
 
-    fn try_from(position_line: String) -> Result<Self, Self::Error> {
-        let mut split = position_line.split(' ');
-        let coords = Coords::try_from_iterator(&mut split)?;
-        let bearing = split
-            .next()
-            .ok_or_else(|| Error::msg("missing bearing"))?
-            .try_into()?;
-        if let Some(_) = split.next() {
-            bail!("grid line has too many fields");
+    let Args {
+        keep_going,
+        render,
+        scents,
+        extent: print_extent,
+        scent_diff: scent_diff_path,
+        summary: summary_on_stderr,
+        summary_on_stdout,
+        oneline,
+        parallel,
+        color,
+        options,
+        ..
+    } = args;
+    // Auto is decided in main(), which knows what the output is.
+    let color = color == Color::Always && options.format == OutputFormat::Text;
+    let before = match &scent_diff_path {
+        Some(path) => {
+            let grid = simulate_robots_buffered(open_file(path)?, options.clone(), |_| Ok(()))?;
+            Some(grid.scents())
         }
-        Ok(Robot { coords, bearing })
-    }
-}
-
-impl Robot {
-    fn is_out_of_bounds(&self, grid: &Grid) -> bool {
-        let Robot { coords, .. } = self;
-        let Grid { max, .. } = grid;
-        0 > coords.x || coords.x > max.x || 0 > coords.y || coords.y > max.y
-    }
-
-    fn go_forwards_unchecked(mut self) -> Self {
-        use Bearing::*;
-        match self.bearing {
-            N => {
-                self.coords.y += 1;
-            }
-            E => {
-                self.coords.x += 1;
-            }
-            S => {
-                self.coords.y -= 1;
+        None => None,
+    };
+    let report_options = options.clone();
+    let mut failures = 0;
+    let mut summary = Summary::default();
+    let mut extent: Option<Extent> = None;
+    let mut robots = vec![];
+    let mut reports = vec![];
+    let grid = drive(input, options, parallel, |outcome| {
+        match outcome {
+            Ok(outcome) => {
+                summary.add(&outcome);
+                match &mut extent {
+                    Some(extent) => extent.merge(&outcome.extent),
+                    None => extent = Some(outcome.extent.clone()),
+                }
+                if let Some(warning) = report_options.warning(&outcome) {
+                    eprintln!("{}", warning);
+                }
+                if report_options.wants(&outcome) {
+                    let mut report = report_options.report(&outcome)?;
+                    if color {
+                        report = colorize(&report, outcome.lost);
+                    }
+                    if oneline {
+                        reports.push(report);
+                    } else {
+                        writeln!(output, "{}", report)?;
+                    }
+                    if report_options.format == OutputFormat::Text && !oneline {
+                        for step in &outcome.trace {
+                            writeln!(output, "    {}", step)?;
+                        }
+                    }
+                }
+                if render {
+                    robots.push(outcome.robot);
+                }
             }
-            W => {
-                self.coords.x -= 1;
+            Err(e) if keep_going => {
+                eprintln!("{}", e);
+                failures += 1;
             }
+            Err(e) => return Err(e),
         }
-        self
+        Ok(())
+    })?;
+    if oneline {
+        writeln!(output, "{}", join_on_one_line(reports.into_iter().map(Ok))?)?;
     }
-
-    fn try_going_forwards(self, grid: &Grid) -> std::result::Result<Robot, Robot> {
-        let next = self.clone().go_forwards_unchecked();
-
-        if next.is_out_of_bounds(grid) {
-            if grid.has_scent(&self) {
-                Ok(self)
-            } else {
-                Err(self)
-            }
-        } else {
-            Ok(next)
-        }
+    if render {
+        write!(output, "{}", render_grid(&grid, &robots))?;
     }
-
-    /// Returns either the position that the robot ended up at or the
-    /// position where it was before it fell off the board.
-    fn try_next_instruction(
-        self,
-        grid: &Grid,
-        instruction: &Instruction,
-    ) -> std::result::Result<Robot, Robot> {
-        match instruction {
-            Instruction::Turn(t) => Ok(Robot {
-                bearing: self.bearing.rotate(t),
-                ..self
-            }),
-            Instruction::F => self.try_going_forwards(grid),
+    if scents {
+        for scent in grid.scents() {
+            writeln!(output, "SCENT {} {}", scent.x, scent.y)?;
         }
     }
-
-    /// Returns either the position that the robot ended up at or the
-    /// position where it was before it fell off the board.
-    fn try_all_instructions(
-        self,
-        grid: &Grid,
-        instructions: &[Instruction],
-    ) -> std::result::Result<Robot, Robot> {
-        let mut current = self;
-        for instruction in instructions {
-            current = current.try_next_instruction(grid, instruction)?;
+    if let Some(extent) = extent.filter(|_| print_extent) {
+        writeln!(output, "EXTENT {}", extent)?;
+    }
+    if let Some(before) = before {
+        for scent in scent_diff(&before, &grid.scents()) {
+            writeln!(output, "NEW SCENT {} {}", scent.x, scent.y)?;
         }
-        Ok(current)
     }
+    if summary_on_stdout {
+        writeln!(output, "{}", summary)?;
+    } else if summary_on_stderr {
+        eprintln!("{}", summary);
+    }
+    if failures > 0 {
+        bail!("{} robots could not be driven", failures);
+    }
+    Ok(())
 }
 
-/// Expects Strings to be trimmed lines, with empty lines omited.
-/// Yields strings representing Robot end positions, ready to be
-/// passed to stdout, or Error if an input line is invalid.
-fn drive_robots(
-    mut lines: impl Iterator<Item = Result<String>>,
-) -> Result<impl Iterator<Item = Result<String>>> {
-    let mut grid = lines
-        .next()
-        .ok_or(Error::msg("input must not be empty"))??
-        .try_into()?;
-
-    let output = lines.tuples().map(
-        move |(position_line, instruction_line): (Result<String>, Result<String>)| {
-            let start: Robot = position_line?.try_into()?;
-            let instructions = instruction_line?
-                .chars()
-                .map(|c| c.try_into())
-                .collect::<Result<Vec<Instruction>>>()?;
-
-            let result = start.try_all_instructions(&grid, &instructions);
-            match result {
-                Ok(alive) => Ok(format!(
-                    "{} {} {}",
-                    alive.coords.x, alive.coords.y, alive.bearing
-                )),
-                Err(dead) => {
-                    grid.apply_scent(&dead);
-                    Ok(format!(
-                        "{} {} {} LOST",
-                        dead.coords.x, dead.coords.y, dead.bearing
-                    ))
-                }
-            }
-        },
-    );
-    return Ok(output);
+/// Like simulate_robots_buffered(), but with the robots on each grid driven at
+/// the same time if `parallel` is set.
+fn drive(
+    input: impl BufRead,
+    options: SimulationConfig,
+    parallel: bool,
+    emit: impl FnMut(Result<RobotOutcome>) -> Result<()>,
+) -> Result<Grid> {
+    #[cfg(feature = "parallel")]
+    {
+        if parallel {
+            let lines = input.lines().map(|l| Ok(l?));
+            return redbadger_challenge::drive_robots_parallel(lines, options, emit);
+        }
+    }
+    #[cfg(not(feature = "parallel"))]
+    let _ = parallel;
+    simulate_robots_buffered(input, options, emit)
 }
 
-fn no_empty_lines(l: &Result<String>) -> bool {
-    match l {
-        Ok(l) => !l.is_empty(),
-        Err(_) => true,
+/// Reads a grid line and then robots, one at a time, with scents left
+/// behind for the next robot. Errors are reported in place of the robot's
+/// result, so that a typo doesn't lose the grid. Stops at the end of the
+/// input.
+fn repl(input: impl BufRead, options: SimulationConfig, mut output: impl Write) -> Result<()> {
+    let result = simulate_robots_buffered(input, options.clone(), |outcome| {
+        match outcome {
+            Ok(outcome) => writeln!(output, "{}", options.report(&outcome)?)?,
+            Err(e) => writeln!(output, "{}", e)?,
+        }
+        output.flush()?;
+        Ok(())
+    });
+    match result {
+        Err(e) if matches!(e.downcast_ref(), Some(ParseError::EmptyInput)) => Ok(()),
+        result => result.map(|_| ()),
     }
 }
 
-fn main() -> anyhow::Result<()> {
-    let stdin = io::stdin();
-    let locked = stdin.lock();
-    // It's a bit annoying that .lines() allocates a new buffer for
-    // each line, but I think it will be easier to refactor this
-    // (once Rust is able to express the lifetime of a re-used buffer)
-    // than it would be to use something other than Iterator to drive
-    // the data flow.
-    let lines = locked.lines();
-
-    // Convert errors to anyhow::Error, and remove empty lines.
-    let lines = lines
-        .map(|l| Ok(l?.trim().to_owned()))
-        .filter(no_empty_lines);
-
-    drive_robots(lines)
-        .flatten_to_iterator()
-        .try_for_each(|result| Ok::<_, Error>(println!("{}", result?)))?;
-
-    Ok(())
+fn main() -> Result<()> {
+    let mut args = parse_args(env::args().skip(1))?;
+    if let Some(robots) = args.generate {
+        print!("{}", generate(args.seed, robots));
+        return Ok(());
+    }
+    let input = open_input(&args)?;
+    let stdout = io::stdout().lock();
+    if args.color == Color::Auto {
+        args.color = if stdout.is_terminal() {
+            Color::Always
+        } else {
+            Color::Never
+        };
+    }
+    run(input, args, stdout)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    fn split(input: &str) -> impl Iterator<Item = Result<String>> + '_ {
-        input
-            .lines()
-            .map(|l| Ok(l.trim().to_owned()))
-            .filter(no_empty_lines)
-    }
+    #[test]
+    fn input_file_is_read_instead_of_stdin() -> Result<()> {
+        let path = env::temp_dir().join(format!("robots-{}.txt", std::process::id()));
+        std::fs::write(&path, "5 3\n1 1 E\nRFRFRFRF\n\n3 2 N\nFRRFLLFFRRFLL\n")?;
 
-    fn join(input: impl Iterator<Item = Result<String>>) -> Result<String> {
-        Ok(input
-            .collect::<Result<Vec<String>>>()?
-            .join("\n")
-            .to_owned())
-    }
+        let args = parse_args(vec!["--input".to_owned(), path.display().to_string()].into_iter())?;
+        let mut output = vec![];
+        let result = run(open_input(&args)?, args, &mut output);
+        std::fs::remove_file(&path)?;
 
-    fn format(input: &str) -> Result<String> {
-        join(split(input).filter(no_empty_lines))
+        result?;
+        assert_eq!(String::from_utf8(output)?, "1 1 E\n3 3 N LOST\n");
+        Ok(())
     }
 
     #[test]
-    fn example_input_produces_example_output() -> Result<()> {
-        let input = r#"
-        5 3
-        1 1 E
-        RFRFRFRF
-        3 2 N
-        FRRFLLFFRRFLL
-        0 3 W
-        LLFFFLFLFL
-        "#;
-        let output = join(drive_robots(split(input)).flatten_to_iterator())?;
-
-        let expected_output = format(
-            r#"
-            1 1 E
-            3 3 N LOST
-            2 3 S
-            "#,
-        )?;
-        assert_eq!(output, expected_output);
+    fn missing_input_file_produces_error() -> Result<()> {
+        let args =
+            parse_args(vec!["--input".to_owned(), "/no/such/robots.txt".to_owned()].into_iter())?;
+        let error = open_input(&args).err().unwrap();
+        assert_eq!(error.to_string(), "could not open /no/such/robots.txt");
         Ok(())
     }
 
     #[test]
-    fn empty_input_produces_error() -> Result<()> {
-        let input = r#""#;
-        let output = drive_robots(split(input))
-            .flatten_to_iterator()
-            .next()
-            .ok_or_else(|| Error::msg("should output something"))?;
+    fn repl_keeps_scents_and_carries_on_after_errors() -> Result<()> {
+        let script = "5 3\n3 2 N\nFRRFLLFFRRFLL\n3 2 Q\nF\n3 2 N\nFRRFLLFFRRFLL\n1 1 E\n";
+        let mut output = vec![];
+        repl(script.as_bytes(), SimulationConfig::default(), &mut output)?;
+        assert_eq!(
+            String::from_utf8(output)?,
+            "3 3 N LOST\n\
+             line 4: Bearing must be one of N, NE, E, SE, S, SW, W, or NW\n\
+             3 2 N\n\
+             line 8: robot position without instructions at end of input\n"
+        );
+
+        let mut output = vec![];
+        repl("".as_bytes(), SimulationConfig::default(), &mut output)?;
+        assert!(output.is_empty());
+        Ok(())
+    }
 
-        assert_eq!(output.unwrap_err().to_string(), "input must not be empty");
+    #[test]
+    fn color_never_matches_plain_output() -> Result<()> {
+        let input = "5 3\n1 1 E\nRFRFRFRF\n3 2 N\nFRRFLLFFRRFLL\n";
+        let mut output = vec![];
+        let args = parse_args(vec!["--color=never".to_owned()].into_iter())?;
+        run(input.as_bytes(), args, &mut output)?;
+        assert_eq!(String::from_utf8(output)?, "1 1 E\n3 3 N LOST\n");
+
+        let mut output = vec![];
+        let args = parse_args(vec!["--color".to_owned(), "always".to_owned()].into_iter())?;
+        run(input.as_bytes(), args, &mut output)?;
+        assert_eq!(
+            String::from_utf8(output)?,
+            "\x1b[32m1 1 E\x1b[0m\n\x1b[31m3 3 N LOST\x1b[0m\n"
+        );
         Ok(())
     }
 }
@@ -0,0 +1,186 @@
+//! Driving the robots on each grid at the same time.
+//!
+//! A grid line in place of a position line starts again on a fresh grid,
+//! so robots on different grids can't affect each other. Robots on the
+//! same grid still have to be driven one at a time, because of scents.
+
+use crate::{Grid, ParsedRobot, RobotOutcome, Simulation, SimulationConfig};
+use anyhow::Result;
+use rayon::prelude::*;
+
+/// A grid, and everything that happens on it.
+struct Section {
+    grid: Grid,
+    /// How many robots came before this grid, for max_robots.
+    robots_before: usize,
+    robots: Vec<Queued>,
+}
+
+/// A robot that has been parsed, and what the Simulation knew about it at
+/// the time, which it needs again when the robot is driven.
+struct Queued {
+    /// The number of the line that finished the robot.
+    line_number: usize,
+    robot_line: usize,
+    robot_instructions: String,
+    parsed: Result<ParsedRobot>,
+}
+
+impl Queued {
+    fn take(simulation: &mut Simulation, parsed: Result<ParsedRobot>) -> Queued {
+        Queued {
+            line_number: simulation.line_number,
+            robot_line: simulation.robot_line,
+            robot_instructions: std::mem::take(&mut simulation.robot_instructions),
+            parsed,
+        }
+    }
+}
+
+/// Like simulate_robots_buffered(), but parses all of `lines` first, and then
+/// drives the robots on each grid on a separate thread. `emit` is still
+/// called in the order of the input, and the outcomes (and the last grid
+/// that is returned) are the same as simulate_robots_buffered() would give.
+pub fn drive_robots_parallel(
+    lines: impl Iterator<Item = Result<String>>,
+    options: SimulationConfig,
+    mut emit: impl FnMut(Result<RobotOutcome>) -> Result<()>,
+) -> Result<Grid> {
+    let sections = split_sections(lines, options.clone())?;
+    let driven: Vec<_> = sections
+        .into_par_iter()
+        .map(|section| {
+            let mut simulation = Simulation {
+                robots: section.robots_before,
+                ..Simulation::with_grid(section.grid, options.clone())
+            };
+            let outcomes: Vec<_> = section
+                .robots
+                .into_iter()
+                .map(|queued| {
+                    simulation.line_number = queued.line_number;
+                    simulation.robot_line = queued.robot_line;
+                    simulation.robot_instructions = queued.robot_instructions;
+                    simulation.drive_parsed(queued.parsed)
+                })
+                .collect();
+            (simulation.grid, outcomes)
+        })
+        .collect();
+
+    let mut last = None;
+    for (grid, outcomes) in driven {
+        for outcome in outcomes {
+            emit(outcome)?;
+        }
+        last = Some(grid);
+    }
+    Ok(last.expect("there is always at least one grid"))
+}
+
+/// Parses `lines` in the same way as simulate_robots(), and sorts the
+/// robots by the grid that they would be driven on.
+fn split_sections(
+    mut lines: impl Iterator<Item = Result<String>>,
+    options: SimulationConfig,
+) -> Result<Vec<Section>> {
+    let mut simulation = Simulation::from_lines(&mut lines, options)?;
+    simulation.retired = Some(vec![]);
+
+    let mut robots = vec![];
+    for line in lines {
+        let parsed = match line {
+            Ok(line) => simulation.parse(Ok(&line)),
+            Err(e) => simulation.parse(Err(e)),
+        };
+        if let Some(parsed) = parsed {
+            let index = sections_so_far(&simulation);
+            robots.push((Queued::take(&mut simulation, parsed), index));
+        }
+    }
+    if let Some(parsed) = simulation.parse_end() {
+        let index = sections_so_far(&simulation);
+        robots.push((Queued::take(&mut simulation, parsed), index));
+    }
+
+    let mut grids = simulation.retired.take().unwrap_or_default();
+    grids.push(simulation.grid);
+    let mut sections: Vec<_> = grids
+        .into_iter()
+        .map(|grid| Section {
+            grid,
+            robots_before: 0,
+            robots: vec![],
+        })
+        .collect();
+    for (before, (queued, index)) in robots.into_iter().enumerate() {
+        let section = &mut sections[index];
+        if section.robots.is_empty() {
+            section.robots_before = before;
+        }
+        section.robots.push(queued);
+    }
+    Ok(sections)
+}
+
+/// The index of the grid that the next robot will be driven on.
+fn sections_so_far(simulation: &Simulation) -> usize {
+    simulation.retired.as_ref().map_or(0, Vec::len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{format_outcome, simulate_robots_buffered};
+
+    fn outcomes(
+        drive: impl FnOnce(&mut dyn FnMut(Result<RobotOutcome>) -> Result<()>) -> Result<Grid>,
+    ) -> Result<(Vec<String>, Grid)> {
+        let mut outcomes = vec![];
+        let grid = drive(&mut |outcome| {
+            outcomes.push(match outcome {
+                Ok(outcome) => format_outcome(&outcome),
+                Err(e) => e.to_string(),
+            });
+            Ok(())
+        })?;
+        Ok((outcomes, grid))
+    }
+
+    #[test]
+    fn parallel_matches_sequential() -> Result<()> {
+        let compare = |input: &str, options: SimulationConfig| -> Result<Vec<String>> {
+            let (sequential, sequential_grid) =
+                outcomes(|emit| simulate_robots_buffered(input.as_bytes(), options.clone(), emit))?;
+            let lines = input.lines().map(|l| Ok(l.to_owned()));
+            let (parallel, parallel_grid) =
+                outcomes(|emit| drive_robots_parallel(lines, options, emit))?;
+            assert_eq!(parallel, sequential);
+            assert_eq!(parallel_grid.scents(), sequential_grid.scents());
+            Ok(parallel)
+        };
+
+        let input = "5 3\n3 2 N\nFRRFLLFFRRFLL\n3 2 N\nFRRFLLFFRRFLL\n\
+                     3 3\n# 1 1\n0 0 N\nFRF\n3 2 N\nFFFX\n3 3 E\nF\n\
+                     2 2\n2 2 N\nF\n2 2 N\nF\n";
+        let options = SimulationConfig::default().with_max_robots(6);
+        assert_eq!(
+            compare(input, options)?,
+            vec![
+                "3 3 N LOST",
+                "3 2 N",
+                "0 1 E",
+                "line 11: instruction must be F, B, L, R, U, S, H, or Tx,y",
+                "3 3 E LOST",
+                "2 2 N LOST",
+                "line 18: robot limit exceeded",
+            ]
+        );
+
+        // Here a grid line also ends the instructions before it.
+        let input = "5 3\n3 2 N\nFRRFLL\nFFRRFLL\n2 2\n2 2 N\nFF\nF\n";
+        let options = SimulationConfig::default().with_multiline_instructions(true);
+        assert_eq!(compare(input, options)?, vec!["3 3 N LOST", "2 2 N LOST"]);
+        Ok(())
+    }
+}
@@ -0,0 +1,56 @@
+//! Pictures of the grid, for debugging.
+
+use crate::{Coords, Grid, Robot};
+
+/// Draws the grid with North at the top. Robots are drawn as arrows, and
+/// cells that have a scent (but no robot) as `*`. If more than one robot
+/// is in a cell then the last one wins.
+pub fn render_grid(grid: &Grid, robots: &[Robot]) -> String {
+    let mut picture = String::new();
+    let rows: Vec<i32> = if grid.y_down {
+        (grid.min.y..=grid.max.y).collect()
+    } else {
+        (grid.min.y..=grid.max.y).rev().collect()
+    };
+    for y in rows {
+        for x in grid.min.x..=grid.max.x {
+            let coords = Coords { x, y };
+            let robot = robots.iter().rev().find(|r| r.coords == coords);
+            picture.push(match robot {
+                Some(robot) => robot.bearing.arrow(),
+                None if grid.scents.is_scented(&coords) => '*',
+                None => '.',
+            });
+        }
+        picture.push('\n');
+    }
+    picture
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    #[test]
+    fn small_grid_with_one_robot() -> Result<()> {
+        let mut grid: Grid = "1 1".parse()?;
+        grid.apply_scent(&"1 1 N".parse()?);
+        let robot: Robot = "0 1 E".parse()?;
+
+        assert_eq!(render_grid(&grid, &[robot]), "→*\n..\n");
+        Ok(())
+    }
+
+    #[test]
+    fn diagonal_robots_show_which_way_they_face() -> Result<()> {
+        let grid: Grid = "1 1".parse()?;
+        let robots = ["0 1 NE", "1 1 SW", "0 0 SE", "1 0 NW"]
+            .iter()
+            .map(|robot| robot.parse())
+            .collect::<Result<Vec<Robot>>>()?;
+
+        assert_eq!(render_grid(&grid, &robots), "↗↙\n↘↖\n");
+        Ok(())
+    }
+}
@@ -0,0 +1,102 @@
+//! Inputs as structured data (JSON, YAML, ...) rather than lines of text.
+
+use crate::{
+    on_line, parse_instruction_line, Grid, LineError, Macros, Robot, RobotError, RobotOutcome,
+    Simulation, SimulationConfig,
+};
+use anyhow::{bail, Result};
+use serde::Deserialize;
+
+/// Everything that the text input describes, apart from obstacles, labels
+/// and macros:
+///
+/// ```json
+/// {"grid": "5 3", "robots": [{"x": 1, "y": 1, "bearing": "E", "instructions": "RFRFRFRF"}]}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct Scenario {
+    pub grid: Grid,
+    pub robots: Vec<ScenarioRobot>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ScenarioRobot {
+    #[serde(flatten)]
+    pub start: Robot,
+    /// The same format as an instruction line.
+    pub instructions: String,
+}
+
+/// Like simulate_robots(), but for a Scenario. Errors are RobotErrors, which
+/// count robots from 1, rather than LineErrors.
+pub fn simulate_scenario(
+    scenario: Scenario,
+    options: SimulationConfig,
+) -> impl Iterator<Item = Result<RobotOutcome>> {
+    let mut simulation = Simulation::with_grid(scenario.grid, options);
+    scenario
+        .robots
+        .into_iter()
+        .zip(1..)
+        .map(move |(robot, number)| {
+            simulation.line_number = number;
+            let parsed = parse_instruction_line(&robot.instructions, &Macros::new()).and_then(
+                |instructions| {
+                    if robot.start.is_out_of_bounds(&simulation.grid) {
+                        bail!("robot starts outside grid");
+                    }
+                    Ok((robot.start, instructions))
+                },
+            );
+            // The Simulation thinks that each robot is a line.
+            let parsed = on_line(number, parsed);
+            simulation
+                .drive_parsed(parsed)
+                .map_err(|e| match e.downcast::<LineError>() {
+                    Ok(LineError { line, error }) => RobotError { robot: line, error }.into(),
+                    Err(e) => e,
+                })
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format_outcome;
+
+    #[test]
+    fn json_scenario_can_be_simulated() -> Result<()> {
+        let json = r#"{
+            "grid": "5 3",
+            "robots": [
+                {"x": 1, "y": 1, "bearing": "E", "instructions": "RFRFRFRF"},
+                {"x": 3, "y": 2, "bearing": "N", "instructions": "FRRFLLFFRRFLL"},
+                {"x": 0, "y": 3, "bearing": "W", "instructions": "LLFFFLFLFL"},
+                {"x": 9, "y": 9, "bearing": "W", "instructions": "F"}
+            ]
+        }"#;
+        let scenario: Scenario = serde_json::from_str(json)?;
+        let output = simulate_scenario(scenario, SimulationConfig::default())
+            .map(|outcome| match outcome {
+                Ok(outcome) => format_outcome(&outcome),
+                Err(e) => e.to_string(),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            output,
+            vec![
+                "1 1 E",
+                "3 3 N LOST",
+                "2 3 S",
+                "robot 4: robot starts outside grid"
+            ]
+        );
+
+        let error = serde_json::from_str::<Scenario>(r#"{"grid": "5 99", "robots": []}"#);
+        assert_eq!(
+            error.unwrap_err().to_string(),
+            "grid coordinate exceeds maximum of 50 at line 1 column 15"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,304 @@
+//! What a lost robot leaves behind, and who it saves.
+
+use crate::Coords;
+#[cfg(not(feature = "std"))]
+use alloc::{
+    collections::{BTreeMap as Map, BTreeSet as Set},
+    vec::Vec,
+};
+use core::fmt::Debug;
+#[cfg(feature = "std")]
+use std::collections::{HashMap as Map, HashSet as Set};
+
+/// Which way a robot is facing, in degrees clockwise from North, so that
+/// square and hexagonal robots can share scents.
+pub type Heading = u16;
+
+/// Decides whether a robot is saved from falling off the grid. The Grid
+/// calls apply_scent() with the last position of each lost robot, and
+/// has_scent() with the position of each robot that is about to fall off.
+/// When a Simulation's robot is really saved, it also calls use_scent().
+/// Policies have to be Send so that grids can be driven on other threads.
+pub trait ScentPolicy: Debug + Send {
+    /// Whether a robot in `coords`, facing `heading`, should ignore an
+    /// instruction that would take it off the grid. This is also used for
+    /// what-ifs like will_be_lost(), so it mustn't change anything.
+    fn has_scent(&self, coords: &Coords, heading: Heading) -> bool;
+
+    /// Records that the scent in `coords` has just saved a robot facing
+    /// `heading`.
+    fn use_scent(&mut self, coords: &Coords, heading: Heading);
+
+    /// Remembers that a robot facing `heading` fell off from `coords`.
+    fn apply_scent(&mut self, coords: &Coords, heading: Heading);
+
+    /// Whether any robot has been lost from `coords`.
+    fn is_scented(&self, coords: &Coords) -> bool;
+
+    /// Every cell that a robot has been lost from, in any order.
+    fn cells(&self) -> Vec<Coords>;
+
+    /// Arguments for apply_scent() that would leave the same scents, in any
+    /// order. Used to copy scents between grids.
+    fn lost_from(&self) -> Vec<(Coords, Heading)>;
+
+    /// Forgets every scent.
+    fn clear(&mut self);
+}
+
+/// For policies that don't care which way robots face.
+fn facing_north(coords: &Coords) -> (Coords, Heading) {
+    (coords.clone(), 0)
+}
+
+/// The rule from the challenge: a scent saves every robot in the same cell,
+/// whichever way it is facing.
+#[derive(Debug, Default)]
+pub struct PerCell(Set<Coords>);
+
+impl ScentPolicy for PerCell {
+    fn has_scent(&self, coords: &Coords, _heading: Heading) -> bool {
+        self.0.contains(coords)
+    }
+
+    fn use_scent(&mut self, _coords: &Coords, _heading: Heading) {}
+
+    fn apply_scent(&mut self, coords: &Coords, _heading: Heading) {
+        self.0.insert(coords.clone());
+    }
+
+    fn is_scented(&self, coords: &Coords) -> bool {
+        self.0.contains(coords)
+    }
+
+    fn cells(&self) -> Vec<Coords> {
+        self.0.iter().cloned().collect()
+    }
+
+    fn lost_from(&self) -> Vec<(Coords, Heading)> {
+        self.0.iter().map(facing_north).collect()
+    }
+
+    fn clear(&mut self) {
+        self.0.clear();
+    }
+}
+
+/// A scent only saves robots that are facing the same way as the robot that
+/// left it, so a robot in a corner can still fall off the other edge.
+#[derive(Debug, Default)]
+pub struct PerCellAndBearing(Set<(Coords, Heading)>);
+
+impl ScentPolicy for PerCellAndBearing {
+    fn has_scent(&self, coords: &Coords, heading: Heading) -> bool {
+        self.0.contains(&(coords.clone(), heading))
+    }
+
+    fn use_scent(&mut self, _coords: &Coords, _heading: Heading) {}
+
+    fn apply_scent(&mut self, coords: &Coords, heading: Heading) {
+        self.0.insert((coords.clone(), heading));
+    }
+
+    fn is_scented(&self, coords: &Coords) -> bool {
+        self.0.iter().any(|(c, _)| c == coords)
+    }
+
+    fn cells(&self) -> Vec<Coords> {
+        let mut cells: Vec<Coords> = self.0.iter().map(|(c, _)| c.clone()).collect();
+        cells.sort();
+        cells.dedup();
+        cells
+    }
+
+    fn lost_from(&self) -> Vec<(Coords, Heading)> {
+        self.0.iter().cloned().collect()
+    }
+
+    fn clear(&mut self) {
+        self.0.clear();
+    }
+}
+
+/// Like PerCell, but each scent fades after saving a robot from falling
+/// off `uses` times, so a later robot can be lost from the same cell.
+#[derive(Debug)]
+pub struct Fading {
+    uses: u32,
+    /// How many more times each scent will save a robot.
+    remaining: Map<Coords, u32>,
+}
+
+impl Fading {
+    pub fn new(uses: u32) -> Fading {
+        Fading {
+            uses,
+            remaining: Map::new(),
+        }
+    }
+}
+
+impl ScentPolicy for Fading {
+    fn has_scent(&self, coords: &Coords, _heading: Heading) -> bool {
+        self.remaining.contains_key(coords)
+    }
+
+    fn use_scent(&mut self, coords: &Coords, _heading: Heading) {
+        match self.remaining.get_mut(coords) {
+            Some(uses) if *uses > 1 => *uses -= 1,
+            Some(_) => {
+                self.remaining.remove(coords);
+            }
+            None => {}
+        }
+    }
+
+    fn apply_scent(&mut self, coords: &Coords, _heading: Heading) {
+        if self.uses > 0 {
+            self.remaining.insert(coords.clone(), self.uses);
+        }
+    }
+
+    fn is_scented(&self, coords: &Coords) -> bool {
+        self.remaining.contains_key(coords)
+    }
+
+    fn cells(&self) -> Vec<Coords> {
+        self.remaining.keys().cloned().collect()
+    }
+
+    /// The scents that they leave will have all of their uses again.
+    fn lost_from(&self) -> Vec<(Coords, Heading)> {
+        self.remaining.keys().map(facing_north).collect()
+    }
+
+    fn clear(&mut self) {
+        self.remaining.clear();
+    }
+}
+
+/// Robots never save each other.
+#[derive(Debug, Default)]
+pub struct NoScents;
+
+impl ScentPolicy for NoScents {
+    fn has_scent(&self, _coords: &Coords, _heading: Heading) -> bool {
+        false
+    }
+
+    fn use_scent(&mut self, _coords: &Coords, _heading: Heading) {}
+
+    fn apply_scent(&mut self, _coords: &Coords, _heading: Heading) {}
+
+    fn is_scented(&self, _coords: &Coords) -> bool {
+        false
+    }
+
+    fn cells(&self) -> Vec<Coords> {
+        Vec::new()
+    }
+
+    fn lost_from(&self) -> Vec<(Coords, Heading)> {
+        Vec::new()
+    }
+
+    fn clear(&mut self) {}
+}
+
+/// The cells in `after` that aren't in `before`, sorted by x and then y.
+/// Comparing Grid::scents() from two runs shows what (for example)
+/// reordering the robots changed.
+pub fn scent_diff(before: &[Coords], after: &[Coords]) -> Vec<Coords> {
+    let before: Set<&Coords> = before.iter().collect();
+    let mut gained: Vec<Coords> = after
+        .iter()
+        .filter(|coords| !before.contains(coords))
+        .cloned()
+        .collect();
+    gained.sort();
+    gained.dedup();
+    gained
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Grid, Instruction, Robot};
+    use anyhow::Result;
+
+    #[test]
+    fn per_bearing_scents_only_save_robots_facing_the_same_way() -> Result<()> {
+        let fall_north: Robot = "5 3 N".parse()?;
+        let fall_east: Robot = "5 3 E".parse()?;
+
+        let mut grid: Grid = "5 3".parse()?;
+        grid.apply_scent(&fall_north);
+        let saved = fall_east
+            .clone()
+            .try_next_instruction(&grid, &Instruction::F);
+        assert_eq!(saved, Ok(fall_east.clone()));
+
+        let mut grid = "5 3"
+            .parse::<Grid>()?
+            .with_scent_policy(PerCellAndBearing::default());
+        grid.apply_scent(&fall_north);
+        let lost = fall_east
+            .clone()
+            .try_next_instruction(&grid, &Instruction::F);
+        assert_eq!(lost, Err(fall_east));
+        let saved = fall_north
+            .clone()
+            .try_next_instruction(&grid, &Instruction::F);
+        assert_eq!(saved, Ok(fall_north));
+        assert_eq!(grid.scents(), vec![Coords { x: 5, y: 3 }]);
+        Ok(())
+    }
+
+    #[test]
+    fn no_scents_saves_nobody() -> Result<()> {
+        let robot: Robot = "5 3 N".parse()?;
+        let mut grid = "5 3".parse::<Grid>()?.with_scent_policy(NoScents);
+        grid.apply_scent(&robot);
+        let lost = robot.clone().try_next_instruction(&grid, &Instruction::F);
+        assert_eq!(lost, Err(robot));
+        assert_eq!(grid.scents(), vec![]);
+        Ok(())
+    }
+
+    #[test]
+    fn scent_diff_lists_newly_scented_cells() {
+        let cell = |x, y| Coords { x, y };
+        let before = [cell(3, 3), cell(0, 0)];
+        let after = [cell(5, 0), cell(0, 0), cell(1, 3), cell(3, 3)];
+        assert_eq!(scent_diff(&before, &after), vec![cell(1, 3), cell(5, 0)]);
+        assert_eq!(scent_diff(&after, &before), vec![]);
+    }
+
+    #[test]
+    fn merged_scents_are_the_union() -> Result<()> {
+        let cell = |x, y| Coords { x, y };
+        let mut left: Grid = "5 3".parse()?;
+        left.apply_scent(&"5 3 N".parse()?);
+        left.apply_scent(&"0 0 S".parse()?);
+        let mut right: Grid = "5 3".parse()?;
+        right.apply_scent(&"0 0 W".parse()?);
+        right.apply_scent(&"2 3 N".parse()?);
+
+        left.merge_scents(&right);
+        assert_eq!(left.scents(), vec![cell(0, 0), cell(2, 3), cell(5, 3)]);
+        assert_eq!(right.scents(), vec![cell(0, 0), cell(2, 3)]);
+
+        let mut by_bearing = "5 3"
+            .parse::<Grid>()?
+            .with_scent_policy(PerCellAndBearing::default());
+        by_bearing.apply_scent(&"5 3 E".parse()?);
+        let mut merged = "5 3"
+            .parse::<Grid>()?
+            .with_scent_policy(PerCellAndBearing::default());
+        merged.merge_scents(&by_bearing);
+        let north: Robot = "5 3 N".parse()?;
+        let lost = north.clone().try_next_instruction(&merged, &Instruction::F);
+        assert_eq!(lost, Err(north));
+        Ok(())
+    }
+}
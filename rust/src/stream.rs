@@ -0,0 +1,111 @@
+//! Driving robots from input that arrives asynchronously, like lines from
+//! a socket.
+
+use crate::{on_line, starts_simulation, ParseError, Simulation, SimulationConfig};
+use anyhow::Result;
+use futures_util::stream::{self, Stream, StreamExt};
+use std::pin::Pin;
+
+/// Like drive_robots_with(), but for a Stream of lines, which yields each
+/// robot's output as soon as its instruction line arrives. The scents last
+/// for as long as the stream does. It doesn't need any particular runtime.
+/// An error before the first robot (like a bad grid line or an empty
+/// input) is the only thing that the stream yields.
+pub fn drive_robots_stream(
+    lines: impl Stream<Item = Result<String>>,
+    options: SimulationConfig,
+) -> impl Stream<Item = Result<String>> {
+    let state = State {
+        lines: Box::pin(lines),
+        options,
+        simulation: None,
+        number: 0,
+        done: false,
+    };
+    stream::unfold(state, |mut state| async move {
+        let output = state.next_output().await?;
+        Some((output, state))
+    })
+}
+
+struct State<S> {
+    lines: Pin<Box<S>>,
+    options: SimulationConfig,
+    /// Set up once the grid line has arrived.
+    simulation: Option<Simulation>,
+    /// The number of the last line before the grid line.
+    number: usize,
+    done: bool,
+}
+
+impl<S: Stream<Item = Result<String>>> State<S> {
+    async fn next_output(&mut self) -> Option<Result<String>> {
+        loop {
+            if self.done {
+                return None;
+            }
+            let line = self.lines.next().await;
+            let outcome = match (self.simulation.as_mut(), line) {
+                (None, Some(line)) => {
+                    self.number += 1;
+                    let started = on_line(self.number, line).and_then(|line| {
+                        if !starts_simulation(&line, self.number, &self.options)? {
+                            return Ok(None);
+                        }
+                        Simulation::new(&line, self.number, self.options.clone()).map(Some)
+                    });
+                    match started {
+                        Ok(simulation) => {
+                            self.simulation = simulation;
+                            continue;
+                        }
+                        Err(e) => {
+                            self.done = true;
+                            return Some(Err(e));
+                        }
+                    }
+                }
+                (None, None) => {
+                    self.done = true;
+                    return Some(Err(ParseError::EmptyInput.into()));
+                }
+                (Some(simulation), Some(Ok(line))) => simulation.feed(Ok(&line)),
+                (Some(simulation), Some(Err(e))) => simulation.feed(Err(e)),
+                (Some(simulation), None) => {
+                    self.done = true;
+                    simulation.finish()
+                }
+            };
+            match outcome {
+                Some(Ok(outcome)) if !self.options.wants(&outcome) => continue,
+                Some(Ok(outcome)) => return Some(self.options.report(&outcome)),
+                Some(Err(e)) => return Some(Err(e)),
+                None => continue,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn drive(input: &str) -> Vec<String> {
+        let lines = tokio_stream::iter(input.lines().map(|l| Ok(l.to_owned())));
+        drive_robots_stream(lines, SimulationConfig::default())
+            .map(|output| output.unwrap_or_else(|e| e.to_string()))
+            .collect()
+            .await
+    }
+
+    #[tokio::test]
+    async fn stream_matches_drive_robots() {
+        let input = "5 3\n1 1 E\nRFRFRFRF\n\n3 2 N\nFRRFLLFFRRFLL\n\n0 3 W\nLLFFFLFLFL\n";
+        assert_eq!(drive(input).await, vec!["1 1 E", "3 3 N LOST", "2 3 S"]);
+        assert_eq!(drive("\n\n").await, vec!["input must not be empty"]);
+        assert_eq!(
+            drive("5\n1 1 E\nF\n").await,
+            vec!["line 1: missing y coordinate"]
+        );
+    }
+}
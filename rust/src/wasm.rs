@@ -0,0 +1,39 @@
+//! An entry point for running in the browser.
+//!
+//! With the `wasm` feature, run() is exported to JavaScript by wasm-bindgen.
+//! Without it, run() is still here (so that it can be tested natively), but
+//! is just a normal function.
+
+use crate::{drive_robots, ResultOfIteratorOfResult};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::wasm_bindgen;
+
+/// Drives the robots in `input`, and returns the output with one line per
+/// robot. If the input is invalid then this returns `{"error": "..."}`
+/// instead, with the first error.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn run(input: &str) -> String {
+    let lines = input.lines().map(|l| Ok(l.to_owned()));
+    let output = drive_robots(lines)
+        .flatten_to_iterator()
+        .collect::<anyhow::Result<Vec<String>>>();
+    match output {
+        Ok(lines) => lines.into_iter().map(|line| line + "\n").collect(),
+        Err(e) => serde_json::json!({ "error": e.to_string() }).to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_returns_the_output_or_an_error() {
+        let input = "5 3\n1 1 E\nRFRFRFRF\n\n3 2 N\nFRRFLLFFRRFLL\n";
+        assert_eq!(run(input), "1 1 E\n3 3 N LOST\n");
+        assert_eq!(
+            run("5 3\n1 1 Q\nF\n"),
+            r#"{"error":"line 2: Bearing must be one of N, NE, E, SE, S, SW, W, or NW"}"#
+        );
+    }
+}
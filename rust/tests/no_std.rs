@@ -0,0 +1,23 @@
+//! The movement logic is supposed to build without `std`, so check that it
+//! still does. This uses its own target directory so that it doesn't fight
+//! with the build that is running the tests.
+
+use std::process::Command;
+
+#[test]
+fn library_builds_without_std() {
+    let output = Command::new(env!("CARGO"))
+        .args(["check", "--lib", "--no-default-features", "--quiet"])
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .env(
+            "CARGO_TARGET_DIR",
+            concat!(env!("CARGO_TARGET_TMPDIR"), "/no_std"),
+        )
+        .output()
+        .expect("could not run cargo");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}